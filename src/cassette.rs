@@ -0,0 +1,87 @@
+use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use crate::{Error, Result};
+
+// one request/response pair, stored as a json line
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Interaction {
+    pub path : String,
+    pub tr_id : String,
+    pub body : Option<serde_json::Value>,
+    pub status : u16,
+    pub response : String,
+    // tr_cont response header, used by paginated inquiries
+    #[serde(default)]
+    pub tr_cont : String,
+}
+
+enum Mode {
+    Record(Mutex<tokio::fs::File>),
+    // recorded interactions and whether each was already served
+    Replay(Mutex<Vec<(Interaction, bool)>>),
+}
+
+// records every exchange of a Session to a file, or serves them back without network
+pub struct Cassette {
+    path : PathBuf,
+    mode : Mode,
+}
+
+impl Cassette {
+    // starts a new recording, replacing any existing file
+    pub async fn record(path: impl AsRef<Path>) -> Result<Cassette> {
+        let file = tokio::fs::File::create(path.as_ref()).await?;
+        Ok(Cassette {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Record(Mutex::new(file)),
+        })
+    }
+
+    pub async fn replay(path: impl AsRef<Path>) -> Result<Cassette> {
+        let data = tokio::fs::read_to_string(path.as_ref()).await?;
+        let mut interactions = Vec::new();
+        for line in data.lines().filter(|line| !line.trim().is_empty()) {
+            let interaction: Interaction = serde_json::from_str(line)?;
+            interactions.push((interaction, false));
+        }
+        Ok(Cassette {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Replay(Mutex::new(interactions)),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self.mode, Mode::Replay(_))
+    }
+
+    pub(crate) async fn record_interaction(&self, interaction: Interaction) -> Result<()> {
+        if let Mode::Record(file) = &self.mode {
+            let mut line = serde_json::to_string(&interaction)?;
+            line.push('\n');
+            let mut file = file.lock().await;
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await?;
+        }
+        Ok(())
+    }
+
+    // serves the first unused interaction with the same path, tr_id and body,
+    // so repeated identical requests get their responses in recorded order
+    pub(crate) async fn replay_interaction(&self, path: &str, tr_id: &str, body: &Option<serde_json::Value>) -> Result<Interaction> {
+        let Mode::Replay(interactions) = &self.mode else {
+            return Err(Error::Config("cassette is not in replay mode".to_string()));
+        };
+        let mut interactions = interactions.lock().await;
+        let found = interactions.iter_mut()
+            .find(|(i, used)| !used && i.path == path && i.tr_id == tr_id && &i.body == body);
+        match found {
+            Some((interaction, used)) => {
+                *used = true;
+                Ok(interaction.clone())
+            },
+            None => Err(Error::Config(format!("{} has no recorded response for {} {}", self.path.display(), tr_id, path))),
+        }
+    }
+}
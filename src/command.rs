@@ -1,5 +1,9 @@
-use serde_json::json;
+// Command::<T>::new() is where every command starts; a Default would only repeat it
+#![allow(clippy::new_without_default)]
 use serde::{Serialize, Deserialize};
+use crate::model::*;
+use crate::database::Column;
+use crate::{Error, Response, Result};
 
 pub enum Period {
     Day,
@@ -19,6 +23,78 @@ impl Period {
     }
 }
 
+// ORD_DVSN of order-cash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrderType {
+    // 지정가
+    Limit,
+    // 시장가
+    #[default]
+    Market,
+    // 조건부지정가, limit until the closing auction then market
+    ConditionalLimit,
+    // 최유리지정가
+    BestPrice,
+    // 장전 시간외, at the previous close
+    PreOpen,
+    // 장후 시간외, at today's close
+    AfterHours,
+}
+
+impl OrderType {
+    fn code(&self) -> &'static str {
+        match self {
+            OrderType::Limit => "00",
+            OrderType::Market => "01",
+            OrderType::ConditionalLimit => "02",
+            OrderType::BestPrice => "03",
+            OrderType::PreOpen => "05",
+            OrderType::AfterHours => "06",
+        }
+    }
+
+    // the rest are priced by the exchange and must send ORD_UNPR 0
+    pub fn takes_price(&self) -> bool {
+        matches!(self, OrderType::Limit | OrderType::ConditionalLimit)
+    }
+}
+
+// SLL_BUY_DVSN_CD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderSide {
+    All,
+    Sell,
+    Buy,
+}
+
+impl OrderSide {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OrderSide::All => "00",
+            OrderSide::Sell => "01",
+            OrderSide::Buy => "02",
+        }
+    }
+}
+
+// CCLD_DVSN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillStatus {
+    All,
+    Filled,
+    Unfilled,
+}
+
+impl FillStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FillStatus::All => "00",
+            FillStatus::Filled => "01",
+            FillStatus::Unfilled => "02",
+        }
+    }
+}
+
 pub enum Sender {
     GET,
     POST,
@@ -38,6 +114,13 @@ pub struct Price {
     fid_input_iscd: String,
 }
 
+#[derive(Serialize, Deserialize, Default)]
+#[allow(non_snake_case)]
+pub struct AskingPrice {
+    fid_cond_mrkt_div_code: String,
+    fid_input_iscd: String,
+}
+
 #[derive(Serialize, Deserialize, Default)]
 #[allow(non_snake_case)]
 pub struct DailyPrice {
@@ -52,7 +135,7 @@ pub struct DailyPrice {
 pub struct Balance {
     fid_cond_mrkt_div_code: String,
     fid_input_iscd: String,
-    CAN0: String,
+    CANO: String,
     ACNT_PRDT_CD: String,
     AFHR_FLPR_YN: String,
     OFL_YN: String,
@@ -68,23 +151,76 @@ pub struct Balance {
 #[derive(Serialize, Deserialize, Default)]
 #[allow(non_snake_case)]
 pub struct OrderBuy {
-    CAN0: String,
+    CANO: String,
     ACNT_PRDT_CD: String,
     PDNO: String,
     ORD_DVSN: String,
     ORD_QTY: String,
     ORD_UNPR: String,
+    #[serde(skip)]
+    order_type: OrderType,
 }
 
 #[derive(Serialize, Deserialize, Default)]
 #[allow(non_snake_case)]
 pub struct OrderSell {
-    CAN0: String,
+    CANO: String,
     ACNT_PRDT_CD: String,
     PDNO: String,
     ORD_DVSN: String,
     ORD_QTY: String,
     ORD_UNPR: String,
+    #[serde(skip)]
+    order_type: OrderType,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[allow(non_snake_case)]
+pub struct OrderModifyCancel {
+    CANO: String,
+    ACNT_PRDT_CD: String,
+    KRX_FWDG_ORD_ORGNO: String,
+    ORGN_ODNO: String,
+    ORD_DVSN: String,
+    RVSE_CNCL_DVSN_CD: String,
+    ORD_QTY: String,
+    ORD_UNPR: String,
+    QTY_ALL_ORD_YN: String,
+    #[serde(skip)]
+    order_type: OrderType,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[allow(non_snake_case)]
+pub struct DailyExecutions {
+    CANO: String,
+    ACNT_PRDT_CD: String,
+    INQR_STRT_DT: String,
+    INQR_END_DT: String,
+    SLL_BUY_DVSN_CD: String,
+    INQR_DVSN: String,
+    PDNO: String,
+    CCLD_DVSN: String,
+    ORD_GNO_BRNO: String,
+    ODNO: String,
+    INQR_DVSN_3: String,
+    INQR_DVSN_1: String,
+    CTX_AREA_FK100: String,
+    CTX_AREA_NK100: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[allow(non_snake_case)]
+pub struct OrderableAmount {
+    CANO: String,
+    ACNT_PRDT_CD: String,
+    PDNO: String,
+    ORD_UNPR: String,
+    ORD_DVSN: String,
+    CMA_EVLU_AMT_ICLD_YN: String,
+    OVRS_ICLD_YN: String,
+    #[serde(skip)]
+    order_type: OrderType,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -103,10 +239,109 @@ pub trait ApiCommand {
     fn tr_id(&self) -> &str;
     fn sender(&self) -> &Sender;
     fn body(&self) -> Option<serde_json::Value>;
+
+    // checked by Session before anything is sent
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // safe to send twice; POSTs place or change orders
+    fn idempotent(&self) -> bool {
+        matches!(self.sender(), Sender::GET)
+    }
+}
+
+// request bodies that can reject invalid field combinations
+pub trait Validate {
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Validate for Price {}
+impl Validate for AskingPrice {}
+impl Validate for DailyPrice {}
+impl Validate for Balance {}
+impl Validate for DailyValue {}
+
+impl Validate for OrderBuy {
+    fn validate(&self) -> Result<()> {
+        validate_order(self.order_type, &self.ORD_QTY, &self.ORD_UNPR)
+    }
+}
+
+impl Validate for OrderSell {
+    fn validate(&self) -> Result<()> {
+        validate_order(self.order_type, &self.ORD_QTY, &self.ORD_UNPR)
+    }
+}
+
+impl Validate for OrderModifyCancel {
+    fn validate(&self) -> Result<()> {
+        if self.KRX_FWDG_ORD_ORGNO.trim().is_empty() || self.ORGN_ODNO.trim().is_empty() {
+            return Err(Error::Invalid("original order number and branch are required".to_string()));
+        }
+        if self.QTY_ALL_ORD_YN == "N" {
+            validate_count(&self.ORD_QTY)?;
+        }
+        if self.RVSE_CNCL_DVSN_CD == REVISE {
+            validate_price(self.order_type, &self.ORD_UNPR)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for DailyExecutions {
+    fn validate(&self) -> Result<()> {
+        let is_date = |d: &str| d.len() == 8 && d.chars().all(|c| c.is_ascii_digit());
+        if !is_date(&self.INQR_STRT_DT) || !is_date(&self.INQR_END_DT) {
+            return Err(Error::Invalid(format!("inquiry dates must be YYYYMMDD, got {:?} - {:?}", self.INQR_STRT_DT, self.INQR_END_DT)));
+        }
+        if self.INQR_STRT_DT > self.INQR_END_DT {
+            return Err(Error::Invalid(format!("inquiry start {} is after end {}", self.INQR_STRT_DT, self.INQR_END_DT)));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for OrderableAmount {
+    fn validate(&self) -> Result<()> {
+        validate_price(self.order_type, &self.ORD_UNPR)
+    }
+}
+
+fn validate_order(order_type: OrderType, count: &str, price: &str) -> Result<()> {
+    validate_count(count)?;
+    validate_price(order_type, price)
+}
+
+fn validate_count(count: &str) -> Result<()> {
+    match count.trim().parse::<u64>() {
+        Ok(count) if count > 0 => Ok(()),
+        _ => Err(Error::Invalid(format!("order quantity must be a positive number, got {:?}", count))),
+    }
+}
+
+fn validate_price(order_type: OrderType, price: &str) -> Result<()> {
+    let price = price.trim().parse::<u64>()
+        .map_err(|_| Error::Invalid(format!("order price must be a number, got {:?}", price)))?;
+    if order_type.takes_price() && price == 0 {
+        return Err(Error::Invalid(format!("{:?} order needs a price", order_type)));
+    }
+    if !order_type.takes_price() && price != 0 {
+        return Err(Error::Invalid(format!("{:?} order must not carry a price", order_type)));
+    }
+    Ok(())
+}
+
+// commands whose response has a typed model
+pub trait TypedCommand: ApiCommand {
+    type Output;
+    fn parse(response: Response) -> Result<Self::Output>;
 }
 
 impl<T> ApiCommand for Command<T>
-where T: Serialize {
+where T: Serialize + Validate {
     fn path(&self) -> &str {
         self.path
     }
@@ -119,6 +354,9 @@ where T: Serialize {
     fn body(&self) -> Option<serde_json::Value> {
         Some(serde_json::to_value(&self.body).unwrap())
     }
+    fn validate(&self) -> Result<()> {
+        self.body.validate()
+    }
 
 }
 
@@ -141,6 +379,39 @@ impl Command<Price> {
     }
 }
 
+impl TypedCommand for Command<Price> {
+    type Output = PriceResponse;
+    fn parse(response: Response) -> Result<Self::Output> {
+        Ok(serde_json::from_value(response.output.unwrap_or_default())?)
+    }
+}
+
+impl Command<AskingPrice> {
+    pub fn new() -> Self {
+        Command {
+            path: "/uapi/domestic-stock/v1/quotations/inquire-asking-price-exp-ccn",
+            tr_id: "FHKST01010200",
+            sender: Sender::GET,
+            body: AskingPrice {
+                fid_cond_mrkt_div_code: "J".to_string(),
+                ..AskingPrice::default()
+            },
+        }
+    }
+
+    pub fn ticker(mut self, ticker: String) -> Self {
+        self.body.fid_input_iscd = ticker;
+        self
+    }
+}
+
+impl TypedCommand for Command<AskingPrice> {
+    type Output = AskingPriceResponse;
+    fn parse(response: Response) -> Result<Self::Output> {
+        AskingPriceResponse::from_outputs(response.output1.unwrap_or_default(), response.output2.unwrap_or_default())
+    }
+}
+
 impl Command<DailyPrice> {
     pub fn new() -> Self {
         Command {
@@ -166,6 +437,14 @@ impl Command<DailyPrice> {
     }
 }
 
+impl TypedCommand for Command<DailyPrice> {
+    type Output = Vec<Column>;
+    fn parse(response: Response) -> Result<Self::Output> {
+        let rows: Vec<DailyPriceRow> = rows(response.output)?;
+        Ok(rows.into_iter().map(Column::from).collect())
+    }
+}
+
 impl Command<Balance> {
     pub fn new() -> Self {
         Command {
@@ -185,7 +464,7 @@ impl Command<Balance> {
     }
 
     pub fn account_no(mut self, account_no: String) -> Self {
-        self.body.CAN0 = account_no;
+        self.body.CANO = account_no;
         self
     }
     pub fn account_cd(mut self, account_cd: String) -> Self {
@@ -194,6 +473,17 @@ impl Command<Balance> {
     }
 }
 
+impl TypedCommand for Command<Balance> {
+    type Output = BalanceResponse;
+    fn parse(response: Response) -> Result<Self::Output> {
+        let summary: Vec<BalanceSummary> = rows(response.output2)?;
+        Ok(BalanceResponse {
+            holdings: rows(response.output1)?,
+            summary: summary.into_iter().next().unwrap_or_default(),
+        })
+    }
+}
+
 impl Command<OrderBuy> {
     pub fn new() -> Self {
         Command {
@@ -201,7 +491,7 @@ impl Command<OrderBuy> {
             tr_id: "TTTC0802U",
             sender: Sender::POST,
             body: OrderBuy {
-                ORD_DVSN: "01".to_string(),
+                ORD_DVSN: OrderType::Market.code().to_string(),
                 ORD_UNPR: "0".to_string(),
                 ..OrderBuy::default()
             }
@@ -209,7 +499,7 @@ impl Command<OrderBuy> {
     }
 
     pub fn account_no(mut self, account_no: String) -> Self {
-        self.body.CAN0 = account_no;
+        self.body.CANO = account_no;
         self
     }
 
@@ -227,6 +517,17 @@ impl Command<OrderBuy> {
         self.body.ORD_QTY = count;
         self
     }
+
+    pub fn order_type(mut self, order_type: OrderType) -> Self {
+        self.body.ORD_DVSN = order_type.code().to_string();
+        self.body.order_type = order_type;
+        self
+    }
+
+    pub fn price(mut self, price: String) -> Self {
+        self.body.ORD_UNPR = price;
+        self
+    }
 }
 
 impl Command<OrderSell> {
@@ -236,7 +537,7 @@ impl Command<OrderSell> {
             tr_id: "TTTC0801U",
             sender: Sender::POST,
             body: OrderSell {
-                ORD_DVSN: "01".to_string(),
+                ORD_DVSN: OrderType::Market.code().to_string(),
                 ORD_UNPR: "0".to_string(),
                 ..OrderSell::default()
             }
@@ -244,7 +545,7 @@ impl Command<OrderSell> {
     }
 
     pub fn account_no(mut self, account_no: String) -> Self {
-        self.body.CAN0 = account_no;
+        self.body.CANO = account_no;
         self
     }
     pub fn account_cd(mut self, account_cd: String) -> Self {
@@ -259,6 +560,226 @@ impl Command<OrderSell> {
         self.body.ORD_QTY = count;
         self
     }
+
+    pub fn order_type(mut self, order_type: OrderType) -> Self {
+        self.body.ORD_DVSN = order_type.code().to_string();
+        self.body.order_type = order_type;
+        self
+    }
+
+    pub fn price(mut self, price: String) -> Self {
+        self.body.ORD_UNPR = price;
+        self
+    }
+}
+
+impl TypedCommand for Command<OrderBuy> {
+    type Output = OrderResponse;
+    fn parse(response: Response) -> Result<Self::Output> {
+        Ok(serde_json::from_value(response.output.unwrap_or_default())?)
+    }
+}
+
+impl TypedCommand for Command<OrderSell> {
+    type Output = OrderResponse;
+    fn parse(response: Response) -> Result<Self::Output> {
+        Ok(serde_json::from_value(response.output.unwrap_or_default())?)
+    }
+}
+
+// RVSE_CNCL_DVSN_CD
+const REVISE: &str = "01";
+const CANCEL: &str = "02";
+
+// cancels the whole remaining quantity unless modify() or count() says otherwise
+impl Command<OrderModifyCancel> {
+    pub fn new() -> Self {
+        Command {
+            path: "/uapi/domestic-stock/v1/trading/order-rvsecncl",
+            tr_id: "TTTC0803U",
+            sender: Sender::POST,
+            body: OrderModifyCancel {
+                ORD_DVSN: OrderType::Limit.code().to_string(),
+                RVSE_CNCL_DVSN_CD: CANCEL.to_string(),
+                ORD_QTY: "0".to_string(),
+                ORD_UNPR: "0".to_string(),
+                QTY_ALL_ORD_YN: "Y".to_string(),
+                order_type: OrderType::Limit,
+                ..OrderModifyCancel::default()
+            }
+        }
+    }
+
+    pub fn account_no(mut self, account_no: String) -> Self {
+        self.body.CANO = account_no;
+        self
+    }
+
+    pub fn account_cd(mut self, account_cd: String) -> Self {
+        self.body.ACNT_PRDT_CD = account_cd;
+        self
+    }
+
+    // KRX_FWDG_ORD_ORGNO and ODNO of the order being changed
+    pub fn original_order(mut self, branch: String, order_no: String) -> Self {
+        self.body.KRX_FWDG_ORD_ORGNO = branch;
+        self.body.ORGN_ODNO = order_no;
+        self
+    }
+
+    pub fn cancel(mut self) -> Self {
+        self.body.RVSE_CNCL_DVSN_CD = CANCEL.to_string();
+        self.body.ORD_UNPR = "0".to_string();
+        self
+    }
+
+    pub fn modify(mut self, order_type: OrderType, price: String) -> Self {
+        self.body.RVSE_CNCL_DVSN_CD = REVISE.to_string();
+        self.body.ORD_DVSN = order_type.code().to_string();
+        self.body.ORD_UNPR = price;
+        self.body.order_type = order_type;
+        self
+    }
+
+    // only this many shares of the remaining quantity
+    pub fn count(mut self, count: String) -> Self {
+        self.body.QTY_ALL_ORD_YN = "N".to_string();
+        self.body.ORD_QTY = count;
+        self
+    }
+}
+
+impl TypedCommand for Command<OrderModifyCancel> {
+    type Output = OrderResponse;
+    fn parse(response: Response) -> Result<Self::Output> {
+        Ok(serde_json::from_value(response.output.unwrap_or_default())?)
+    }
+}
+
+// TTTC8001R covers the last three months; older history is CTSC9115R
+const RECENT_EXECUTIONS_TR_ID: &str = "TTTC8001R";
+const PAST_EXECUTIONS_TR_ID: &str = "CTSC9115R";
+
+impl Command<DailyExecutions> {
+    pub fn new() -> Self {
+        Command {
+            path: "/uapi/domestic-stock/v1/trading/inquire-daily-ccld",
+            tr_id: RECENT_EXECUTIONS_TR_ID,
+            sender: Sender::GET,
+            body: DailyExecutions {
+                SLL_BUY_DVSN_CD: OrderSide::All.as_str().to_string(),
+                INQR_DVSN: "00".to_string(),
+                CCLD_DVSN: FillStatus::All.as_str().to_string(),
+                INQR_DVSN_3: "00".to_string(),
+                ..DailyExecutions::default()
+            }
+        }
+    }
+
+    pub fn account_no(mut self, account_no: String) -> Self {
+        self.body.CANO = account_no;
+        self
+    }
+
+    pub fn account_cd(mut self, account_cd: String) -> Self {
+        self.body.ACNT_PRDT_CD = account_cd;
+        self
+    }
+
+    // a range starting more than three months ago switches to the past-history tr_id,
+    // which also covers recent days
+    pub fn date(mut self, start: String, end: String) -> Self {
+        let today = chrono::Utc::now().with_timezone(&chrono::FixedOffset::east_opt(3600 * 9).unwrap()).date_naive();
+        let recent_since = today.checked_sub_months(chrono::Months::new(3)).unwrap_or(today);
+        self.tr_id = match chrono::NaiveDate::parse_from_str(&start, "%Y%m%d") {
+            Ok(start) if start < recent_since => PAST_EXECUTIONS_TR_ID,
+            _ => RECENT_EXECUTIONS_TR_ID,
+        };
+        self.body.INQR_STRT_DT = start;
+        self.body.INQR_END_DT = end;
+        self
+    }
+
+    pub fn side(mut self, side: OrderSide) -> Self {
+        self.body.SLL_BUY_DVSN_CD = side.as_str().to_string();
+        self
+    }
+
+    pub fn fill_status(mut self, status: FillStatus) -> Self {
+        self.body.CCLD_DVSN = status.as_str().to_string();
+        self
+    }
+
+    pub fn ticker(mut self, ticker: String) -> Self {
+        self.body.PDNO = ticker;
+        self
+    }
+
+    // a single order, by the branch and number from OrderResponse
+    pub fn order(mut self, branch: String, order_no: String) -> Self {
+        self.body.ORD_GNO_BRNO = branch;
+        self.body.ODNO = order_no;
+        self
+    }
+}
+
+// use execute_all_typed to collect every page
+impl TypedCommand for Command<DailyExecutions> {
+    type Output = Vec<Execution>;
+    fn parse(response: Response) -> Result<Self::Output> {
+        let rows: Vec<Execution> = rows(response.output1)?;
+        Ok(rows.into_iter().filter(|r| !r.order_no.is_empty()).collect())
+    }
+}
+
+impl Command<OrderableAmount> {
+    pub fn new() -> Self {
+        Command {
+            path: "/uapi/domestic-stock/v1/trading/inquire-psbl-order",
+            tr_id: "TTTC8908R",
+            sender: Sender::GET,
+            body: OrderableAmount {
+                ORD_UNPR: "0".to_string(),
+                ORD_DVSN: OrderType::Market.code().to_string(),
+                CMA_EVLU_AMT_ICLD_YN: "N".to_string(),
+                OVRS_ICLD_YN: "N".to_string(),
+                ..OrderableAmount::default()
+            }
+        }
+    }
+
+    pub fn account_no(mut self, account_no: String) -> Self {
+        self.body.CANO = account_no;
+        self
+    }
+
+    pub fn account_cd(mut self, account_cd: String) -> Self {
+        self.body.ACNT_PRDT_CD = account_cd;
+        self
+    }
+
+    pub fn ticker(mut self, ticker: String) -> Self {
+        self.body.PDNO = ticker;
+        self
+    }
+
+    pub fn order_type(mut self, order_type: OrderType) -> Self {
+        self.body.ORD_DVSN = order_type.code().to_string();
+        self.body.order_type = order_type;
+        self
+    }
+
+    pub fn price(mut self, price: String) -> Self {
+        self.body.ORD_UNPR = price;
+        self
+    }
+}
+
+impl TypedCommand for Command<OrderableAmount> {
+    type Output = OrderableAmountResponse;
+    fn parse(response: Response) -> Result<Self::Output> {
+        Ok(serde_json::from_value(response.output.unwrap_or_default())?)
+    }
 }
 
 impl Command<DailyValue> {
@@ -289,3 +810,12 @@ impl Command<DailyValue> {
         self
     }
 }
+
+impl TypedCommand for Command<DailyValue> {
+    type Output = Vec<Column>;
+    fn parse(response: Response) -> Result<Self::Output> {
+        // rows without a date pad the end of short ranges
+        let rows: Vec<DailyChartRow> = rows(response.output2)?;
+        Ok(rows.into_iter().filter(|r| !r.date.is_empty()).map(Column::from).collect())
+    }
+}
@@ -0,0 +1,270 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDate;
+use crate::environment::Environment;
+use crate::strategy::{Account, MomentumParams, Strategy};
+use crate::{Error, Result};
+
+// settings of the trading binary, read from a TOML file and overridden by env vars:
+//   ENVIRONMENT, DOMAIN, DATA_DIR, TOKEN_DIR, BACKTEST_START, BACKTEST_END
+//   APP_KEY, APP_SECRET, ACCOUNT_NO, ACCOUNT_CD, CAPITAL for the first account
+//   <NAME>_APP_KEY, <NAME>_APP_SECRET, ... for the account called <name>
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    // inferred from domain when not set, see environment()
+    pub environment : Option<Environment>,
+    // overrides the REST domain of the environment
+    pub domain : Option<String>,
+    #[serde(default = "default_data_dir")]
+    pub data_dir : PathBuf,
+    #[serde(default = "default_token_dir")]
+    pub token_dir : PathBuf,
+    #[serde(default)]
+    pub accounts : Vec<AccountConfig>,
+    #[serde(default)]
+    pub strategies : Vec<StrategyConfig>,
+    #[serde(default)]
+    pub backtest : BacktestConfig,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AccountConfig {
+    pub name : String,
+    // better left to env vars than committed to the file
+    #[serde(default)]
+    pub app_key : String,
+    #[serde(default)]
+    pub app_secret : String,
+    #[serde(default)]
+    pub account_no : String,
+    #[serde(default)]
+    pub account_cd : String,
+    #[serde(default = "default_capital")]
+    pub capital : i32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StrategyConfig {
+    pub kind : String,
+    pub account : String,
+    #[serde(default)]
+    pub params : toml::Table,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct BacktestConfig {
+    pub start : u32,
+    pub end : u32,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        BacktestConfig {
+            start: 20230101,
+            end: 20230512,
+        }
+    }
+}
+
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("./data")
+}
+
+fn default_token_dir() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_capital() -> i32 {
+    2500000
+}
+
+impl Config {
+    // a missing file is fine as long as env vars describe at least one account
+    pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(data) => Self::parse(&data)
+                .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::parse("")?,
+            Err(e) => return Err(Error::Config(format!("{}: {}", path.display(), e))),
+        };
+        config.apply_env(|key| env::var(key).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn parse(data: &str) -> Result<Config> {
+        toml::from_str(data).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(environment) = var("ENVIRONMENT") {
+            self.environment = Some(environment.parse()?);
+        }
+        if let Some(domain) = var("DOMAIN") {
+            self.domain = Some(domain);
+        }
+        if let Some(dir) = var("DATA_DIR") {
+            self.data_dir = dir.into();
+        }
+        if let Some(dir) = var("TOKEN_DIR") {
+            self.token_dir = dir.into();
+        }
+        if let Some(start) = var("BACKTEST_START") {
+            self.backtest.start = parse_env("BACKTEST_START", &start)?;
+        }
+        if let Some(end) = var("BACKTEST_END") {
+            self.backtest.end = parse_env("BACKTEST_END", &end)?;
+        }
+
+        // unprefixed variables describe the first account, as main.rs always read them
+        if self.accounts.is_empty() && var("ACCOUNT_NO").is_some() {
+            self.accounts.push(AccountConfig {
+                name: "default".to_string(),
+                capital: default_capital(),
+                ..AccountConfig::default()
+            });
+        }
+        for (i, account) in self.accounts.iter_mut().enumerate() {
+            let prefix = format!("{}_", account.name.to_ascii_uppercase().replace('-', "_"));
+            let lookup = |key: &str| var(&format!("{}{}", prefix, key))
+                .or_else(|| if i == 0 { var(key) } else { None });
+            if let Some(v) = lookup("APP_KEY") {
+                account.app_key = v;
+            }
+            if let Some(v) = lookup("APP_SECRET") {
+                account.app_secret = v;
+            }
+            if let Some(v) = lookup("ACCOUNT_NO") {
+                account.account_no = v;
+            }
+            if let Some(v) = lookup("ACCOUNT_CD") {
+                account.account_cd = v;
+            }
+            if let Some(v) = lookup("CAPITAL") {
+                account.capital = parse_env(&format!("{}CAPITAL", prefix), &v)?;
+            }
+        }
+
+        // without explicit strategies run the ones main.rs always ran
+        if self.strategies.is_empty() {
+            if let Some(account) = self.accounts.first() {
+                for kind in ["test", "price_momentum"] {
+                    self.strategies.push(StrategyConfig {
+                        kind: kind.to_string(),
+                        account: account.name.clone(),
+                        params: toml::Table::new(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        // real tr_ids sent to the paper server (or the reverse) fail on every trade
+        if let (Some(environment), Some(domain)) = (self.environment, &self.domain) {
+            if !environment.matches_domain(domain) {
+                return Err(Error::Config(format!("environment {:?} does not match domain {}", environment, domain)));
+            }
+        }
+        if self.accounts.is_empty() {
+            return Err(Error::Config("no accounts configured".to_string()));
+        }
+        let mut names = HashSet::new();
+        for account in &self.accounts {
+            if !names.insert(account.name.as_str()) {
+                return Err(Error::Config(format!("account {} is configured twice", account.name)));
+            }
+            let missing = [
+                ("app_key", &account.app_key),
+                ("app_secret", &account.app_secret),
+                ("account_no", &account.account_no),
+                ("account_cd", &account.account_cd),
+            ];
+            for (field, value) in missing {
+                if value.trim().is_empty() {
+                    return Err(Error::Config(format!("account {}: {} is not set", account.name, field)));
+                }
+            }
+            if account.account_no.len() != 8 || !account.account_no.chars().all(|c| c.is_ascii_digit()) {
+                return Err(Error::Config(format!("account {}: account_no must be 8 digits", account.name)));
+            }
+            if account.account_cd.len() != 2 || !account.account_cd.chars().all(|c| c.is_ascii_digit()) {
+                return Err(Error::Config(format!("account {}: account_cd must be 2 digits", account.name)));
+            }
+            if account.capital < 0 {
+                return Err(Error::Config(format!("account {}: capital must not be negative", account.name)));
+            }
+        }
+
+        for strategy in &self.strategies {
+            if !names.contains(strategy.account.as_str()) {
+                return Err(Error::Config(format!("strategy {}: unknown account {}", strategy.kind, strategy.account)));
+            }
+            strategy.to_strategy(Account::new(String::new(), String::new(), 0))?;
+        }
+
+        let start = parse_date("backtest.start", self.backtest.start)?;
+        let end = parse_date("backtest.end", self.backtest.end)?;
+        if start > end {
+            return Err(Error::Config(format!("backtest.start {} is after backtest.end {}", self.backtest.start, self.backtest.end)));
+        }
+        if !self.data_dir.is_dir() {
+            return Err(Error::Config(format!("data_dir {} is not a directory", self.data_dir.display())));
+        }
+        Ok(())
+    }
+
+    // existing .env setups only point DOMAIN at openapivts, so that alone selects paper
+    pub fn environment(&self) -> Environment {
+        match (self.environment, &self.domain) {
+            (Some(environment), _) => environment,
+            (None, Some(domain)) => Environment::from_domain(domain),
+            (None, None) => Environment::default(),
+        }
+    }
+
+    pub fn account(&self, name: &str) -> Option<&AccountConfig> {
+        self.accounts.iter().find(|a| a.name == name)
+    }
+}
+
+impl AccountConfig {
+    pub fn to_account(&self) -> Account {
+        Account::new(self.account_no.clone(), self.account_cd.clone(), self.capital)
+    }
+}
+
+impl StrategyConfig {
+    pub fn to_strategy(&self, account: Account) -> Result<Strategy> {
+        let invalid = |e: toml::de::Error| Error::Config(format!("strategy {}: {}", self.kind, e.message()));
+        match self.kind.as_str() {
+            "test" if self.params.is_empty() => Ok(Strategy::Test(account)),
+            "test" => Err(Error::Config("strategy test takes no params".to_string())),
+            "price_momentum" => {
+                let params: MomentumParams = self.params.clone().try_into().map_err(invalid)?;
+                if params.skip >= params.lookback {
+                    return Err(Error::Config("strategy price_momentum: skip must be less than lookback".to_string()));
+                }
+                Ok(Strategy::PriceMomentum(account, params))
+            },
+            kind => Err(Error::Config(format!("unknown strategy kind {}", kind))),
+        }
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value.trim().parse().map_err(|_| Error::Config(format!("{} has an invalid value: {}", key, value)))
+}
+
+fn parse_date(field: &str, date: u32) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&date.to_string(), "%Y%m%d")
+        .map_err(|_| Error::Config(format!("{} must be a YYYYMMDD date, got {}", field, date)))
+}
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, cmp::Ordering};
 use serde::Deserialize;
 use std::fs::File;
+use std::path::Path;
 
 #[derive(Debug, Deserialize)]
 pub struct Column {
@@ -21,11 +22,19 @@ pub struct DataBase {
 }
 
 impl DataBase {
+    // reads the disk, which a Default impl should not
+    #[allow(clippy::new_without_default)]
     pub fn new() -> DataBase {
-        let stock_list = Self::load_list();
+        Self::from_dir("./data")
+    }
+
+    // loads list.csv and one <ticker>.csv per stock from `dir`
+    pub fn from_dir(dir: impl AsRef<Path>) -> DataBase {
+        let dir = dir.as_ref();
+        let stock_list = Self::load_list(dir);
         let mut db : HashMap<String, Vec<Column>> = HashMap::new();
         for stock in &stock_list {
-            let column = Self::load_data(stock);
+            let column = Self::load_data(dir, stock);
             db.insert(stock.to_owned(), column);
         }
 
@@ -40,14 +49,14 @@ impl DataBase {
         database
     }
 
-    fn load_list() -> Vec<String> {
-        let file_path = "./data/list.csv";
+    fn load_list(dir: &Path) -> Vec<String> {
+        let file_path = dir.join("list.csv");
         let file = File::open(file_path).unwrap();
         let mut reader = csv::Reader::from_reader(file);
         let mut records : Vec<String> = Vec::new();
         for record in reader.records() {
             let record = record.unwrap();
-            if record.len() > 0 {
+            if !record.is_empty() {
                 records.push(record[0].to_string());
             }
         }
@@ -66,8 +75,8 @@ impl DataBase {
         results
     }
 
-    fn load_data(stock_no: &str) -> Vec<Column> {
-        let file_path = format!("./data/{}.csv", stock_no);
+    fn load_data(dir: &Path, stock_no: &str) -> Vec<Column> {
+        let file_path = dir.join(format!("{}.csv", stock_no));
         let file = File::open(file_path).unwrap();
         let mut reader = csv::ReaderBuilder::new()
             .delimiter(b' ')
@@ -82,14 +91,12 @@ impl DataBase {
 
     // search date from date_list with lower bound
     pub fn idx_from_date(&self, date: u32) -> usize {
-        let position = self.date_list
+        self.date_list
             .binary_search_by(|d| match d.cmp(&date) {
                 Ordering::Equal => Ordering::Greater,
                 ord => ord,
             })
-            .or_else(|idx| Ok::<usize, usize>(idx))
-            .unwrap();
-        position
+            .unwrap_or_else(|idx| idx)
     }
 
     pub fn get_columns(&self, stock_no: &str) -> Option<&Vec<Column>> {
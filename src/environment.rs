@@ -0,0 +1,78 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer};
+use crate::Error;
+use crate::rate_limit::RateLimiter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Environment {
+    #[default]
+    Real,
+    // 모의투자
+    Paper,
+}
+
+impl Environment {
+    pub fn domain(&self) -> &'static str {
+        match self {
+            Environment::Real => "https://openapi.koreainvestment.com:9443",
+            Environment::Paper => "https://openapivts.koreainvestment.com:29443",
+        }
+    }
+
+    pub fn ws_domain(&self) -> &'static str {
+        match self {
+            Environment::Real => "ws://ops.koreainvestment.com:21000",
+            Environment::Paper => "ws://ops.koreainvestment.com:31000",
+        }
+    }
+
+    // the paper server lives on openapivts.*; anything else is treated as real
+    pub fn from_domain(domain: &str) -> Self {
+        if domain.contains("openapivts") {
+            Environment::Paper
+        } else {
+            Environment::Real
+        }
+    }
+
+    // false only for the KIS server of the other environment; mocks and proxies pass
+    pub fn matches_domain(&self, domain: &str) -> bool {
+        !domain.contains("koreainvestment.com") || Environment::from_domain(domain) == *self
+    }
+
+    pub fn rate_limiter(&self) -> RateLimiter {
+        match self {
+            Environment::Real => RateLimiter::real(),
+            Environment::Paper => RateLimiter::paper(),
+        }
+    }
+
+    // trading tr_ids are prefixed T/J/C on the real server and V on the paper server
+    // (TTTC0802U -> VTTC0802U); quotation tr_ids are shared
+    pub fn tr_id<'a>(&self, tr_id: &'a str) -> Cow<'a, str> {
+        match self {
+            Environment::Paper if tr_id.starts_with(['T', 'J', 'C']) => Cow::Owned(format!("V{}", &tr_id[1..])),
+            _ => Cow::Borrowed(tr_id),
+        }
+    }
+}
+
+impl FromStr for Environment {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "real" | "prod" => Ok(Environment::Real),
+            "paper" | "virtual" | "vts" => Ok(Environment::Paper),
+            _ => Err(Error::Config(format!("unknown environment: {}", s))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Environment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
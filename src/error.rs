@@ -0,0 +1,90 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    // connection, timeout and other failures below the HTTP layer
+    Transport(reqwest::Error),
+    // server answered with a non-success HTTP status
+    Status { status: u16, body: String },
+    // response body could not be decoded
+    Decode(serde_json::Error),
+    // KIS answered with a non-zero rt_cd
+    Api { rt_cd: String, msg_cd: String, msg: String },
+    // access token / approval key could not be issued
+    Auth(String),
+    // missing or invalid settings
+    Config(String),
+    // command rejected before it was sent
+    Invalid(String),
+    // execute_all gave up while the server still had pages left
+    Truncated { pages: usize },
+    // local file access (token cache etc.)
+    Io(std::io::Error),
+}
+
+impl Error {
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Error::Transport(_))
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Status { status, .. } => Some(*status),
+            Error::Transport(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+
+    pub fn msg_cd(&self) -> Option<&str> {
+        match self {
+            Error::Api { msg_cd, .. } => Some(msg_cd),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(e) => write!(f, "transport error: {}", e),
+            Error::Status { status, body } => write!(f, "http status {}: {}", status, body),
+            Error::Decode(e) => write!(f, "decode error: {}", e),
+            Error::Api { rt_cd, msg_cd, msg } => write!(f, "api error (rt_cd {}, {}): {}", rt_cd, msg_cd, msg),
+            Error::Auth(msg) => write!(f, "auth error: {}", msg),
+            Error::Config(msg) => write!(f, "config error: {}", msg),
+            Error::Invalid(msg) => write!(f, "invalid command: {}", msg),
+            Error::Truncated { pages } => write!(f, "more pages left after {} pages", pages),
+            Error::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    // the url carries GET query strings, account numbers included
+    fn from(e: reqwest::Error) -> Self {
+        Error::Transport(e.without_url())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
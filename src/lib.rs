@@ -1,34 +1,67 @@
 use reqwest::{Client, header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE, ACCEPT_CHARSET}};
 use serde::{Serialize, Deserialize};
 use serde_json::json;
-use std::path::Path;
-use std::fs;
-use std::ops::Add;
-use chrono::{Utc, NaiveDateTime, FixedOffset};
-use command::{ApiCommand, Sender};
+use futures_util::stream::{self, StreamExt};
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+use tracing::{field, Instrument};
+use command::{ApiCommand, TypedCommand, Sender};
+use token_store::{Token, TokenStore, FileTokenStore};
+use rate_limit::RateLimiter;
+use retry::RetryPolicy;
+use cassette::{Cassette, Interaction};
+pub mod cassette;
 pub mod command;
+pub mod config;
 pub mod database;
+pub mod environment;
+pub mod error;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod model;
+pub mod rate_limit;
+pub mod redact;
+pub mod registry;
+pub mod response;
+pub mod retry;
 pub mod strategy;
 pub mod time_runner;
+pub mod token_store;
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+pub use environment::Environment;
+pub use error::Error;
+pub use response::Response;
+
+pub type Result<T> = std::result::Result<T, Error>;
 
 pub struct Session {
     app_key : String,
     app_secret : String,
     domain : String,
-    token : Token,
+    environment : Environment,
+    token : RwLock<Token>,
+    token_store : Box<dyn TokenStore>,
+    // access token this session requested itself, the only one close() may revoke
+    issued_token : std::sync::Mutex<String>,
+    rate_limiter : RateLimiter,
+    retry_policy : RetryPolicy,
+    use_hashkey : bool,
+    cassette : Option<Cassette>,
     client : Client,
     header : HeaderMap,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
-struct Token {
-    access_token : String,
-    #[serde(alias = "access_token_token_expired")]
-    expired : String,
-    token_type : String,
-    expires_in : u32,
+pub struct SessionBuilder {
+    app_key : String,
+    app_secret : String,
+    domain : Option<String>,
+    environment : Environment,
+    token_store : Box<dyn TokenStore>,
+    rate_limiter : Option<RateLimiter>,
+    retry_policy : RetryPolicy,
+    use_hashkey : bool,
+    cassette : Option<Cassette>,
+    timeout : std::time::Duration,
 }
 
 #[derive(Deserialize, Serialize, Debug, Default)]
@@ -37,70 +70,178 @@ pub struct WsKey {
 }
 
 const TOKEN_FILE_PATH: &str = "token.json";
+// execute_all fails with Error::Truncated rather than follow more pages than this
+const MAX_PAGES: usize = 100;
+// a stalled connection becomes a (retryable) timeout error after this long
+const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+// msg_cd KIS answers with when the access token is expired or invalid
+const TOKEN_ERROR_CODES: [&str; 2] = ["EGW00123", "EGW00121"];
 
-impl Token {
-    fn is_expired(&self) -> bool {
-        let fixed = FixedOffset::east_opt(3600 * 9).unwrap();
-        let now: NaiveDateTime = Utc::now().naive_utc().add(fixed);
-        let expired = NaiveDateTime::parse_from_str(&self.expired, "%Y-%m-%d %H:%M:%S")
-            .expect("Failed to parse specific time");
-
-        now > expired
+impl SessionBuilder {
+    pub fn new(app_key: String, app_secret: String, domain: String) -> Self {
+        let environment = Environment::from_domain(&domain);
+        Self::with_environment(app_key, app_secret, environment).domain(domain)
     }
-}
 
-impl Session {
-    pub async fn new(app_key: String, app_secret: String, domain: String) -> Result<Session> {
-        let mut session = Session {
+    pub fn with_environment(app_key: String, app_secret: String, environment: Environment) -> Self {
+        SessionBuilder {
             app_key,
             app_secret,
-            domain,
-            token: Token::default(),
-            client: Client::new(),
+            domain: None,
+            environment,
+            token_store: Box::new(FileTokenStore::new(TOKEN_FILE_PATH)),
+            rate_limiter: None,
+            retry_policy: RetryPolicy::default(),
+            use_hashkey: false,
+            cassette: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    // overrides the REST domain of the environment
+    pub fn domain(mut self, domain: String) -> Self {
+        self.domain = Some(domain);
+        self
+    }
+
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    pub fn token_store(mut self, token_store: impl TokenStore + 'static) -> Self {
+        self.token_store = Box::new(token_store);
+        self
+    }
+
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // attach a hashkey header (from /uapi/hashkey) to every POST body
+    pub fn hashkey(mut self, use_hashkey: bool) -> Self {
+        self.use_hashkey = use_hashkey;
+        self
+    }
+
+    // record every exchange, or answer from a recording without touching the network
+    pub fn cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    // limit for a whole request, from connecting until the body is read
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn build(self) -> Result<Session> {
+        if let Some(domain) = self.domain.as_ref().filter(|d| !self.environment.matches_domain(d)) {
+            return Err(Error::Config(format!("environment {:?} does not match domain {}", self.environment, domain)));
+        }
+        let mut session = Session {
+            app_key: self.app_key,
+            app_secret: self.app_secret,
+            domain: self.domain.unwrap_or_else(|| self.environment.domain().to_string()),
+            environment: self.environment,
+            token: RwLock::new(Token::default()),
+            token_store: self.token_store,
+            issued_token: std::sync::Mutex::new(String::new()),
+            rate_limiter: self.rate_limiter.unwrap_or_else(|| self.environment.rate_limiter()),
+            retry_policy: self.retry_policy,
+            use_hashkey: self.use_hashkey,
+            cassette: self.cassette,
+            client: Client::builder().timeout(self.timeout).build()?,
             header: HeaderMap::new(),
         };
 
-        session.load_access_token().await;
-        session.header.insert(CONTENT_TYPE, HeaderValue::from_str("application/json").unwrap());
-        session.header.insert(ACCEPT, HeaderValue::from_str("text/plain").unwrap());
-        session.header.insert(ACCEPT_CHARSET, HeaderValue::from_str("UTF-8").unwrap());
-        session.header.insert("authorization", HeaderValue::from_str(&format!("{} {}", session.token.token_type, session.token.access_token)).unwrap());
-        session.header.insert("appkey", HeaderValue::from_str(&session.app_key).unwrap());
-        session.header.insert("appsecret", HeaderValue::from_str(&session.app_secret).unwrap());
+        if !session.cassette.as_ref().is_some_and(Cassette::is_replay) {
+            session.load_access_token().await?;
+        }
+        session.header.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        session.header.insert(ACCEPT, HeaderValue::from_static("text/plain"));
+        session.header.insert(ACCEPT_CHARSET, HeaderValue::from_static("UTF-8"));
+        session.header.insert("appkey", header_value(&session.app_key)?);
+        session.header.insert("appsecret", header_value(&session.app_secret)?);
         Ok(session)
     }
-    
-    async fn read_token_from_file(&self) -> Option<Token> {
-        if Path::new(TOKEN_FILE_PATH).exists() {
-            let data = fs::read_to_string(TOKEN_FILE_PATH).ok()?;
-            let token: Token = serde_json::from_str(&data).ok()?;
-            Some(token)
-        } else {
-            None
-        }
+}
+
+impl Session {
+    pub async fn new(app_key: String, app_secret: String, domain: String) -> Result<Session> {
+        Self::builder(app_key, app_secret, domain).build().await
     }
 
-    async fn save_token_to_file(&self) {
-        let data = serde_json::to_string(&self.token).unwrap();
-        fs::write(TOKEN_FILE_PATH, data).expect("Unable to write token to file");
+    pub fn builder(app_key: String, app_secret: String, domain: String) -> SessionBuilder {
+        SessionBuilder::new(app_key, app_secret, domain)
     }
 
-    async fn load_access_token(&mut self) {
-        match self.read_token_from_file().await {
-            Some(token) => {
-                self.token = token;
-                if self.token.is_expired() {
-                    self.token = self.request_token().await
-                        .expect("request token failed");
-                    self.save_token_to_file().await;
-                }
-            },
-            None => {
-                self.token = self.request_token().await
-                    .expect("request token failed");
-                self.save_token_to_file().await;
-            },
+    pub fn builder_for(app_key: String, app_secret: String, environment: Environment) -> SessionBuilder {
+        SessionBuilder::with_environment(app_key, app_secret, environment)
+    }
+
+    pub fn environment(&self) -> Environment {
+        self.environment
+    }
+
+    pub fn ws_domain(&self) -> &'static str {
+        self.environment.ws_domain()
+    }
+
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    async fn load_access_token(&self) -> Result<()> {
+        let token = match self.token_store.load(&self.app_key).await? {
+            Some(token) if !token.is_expired() => token,
+            _ => self.issue_token(None).await?,
         };
+        *self.token.write().await = token;
+        Ok(())
+    }
+
+    // KIS allows roughly one issuance a minute, so only the lock holder asks for a new
+    // token and everyone queued behind it picks up what it stored.
+    async fn issue_token(&self, rejected: Option<&str>) -> Result<Token> {
+        let _lock = self.token_store.lock(&self.app_key).await?;
+        if let Some(token) = self.token_store.load(&self.app_key).await? {
+            if !token.is_expired() && rejected != Some(token.authorization().as_str()) {
+                return Ok(token);
+            }
+        }
+        let token = self.request_token().await?;
+        self.token_store.save(&self.app_key, &token).await?;
+        *self.issued_token.lock().unwrap() = token.access_token().to_string();
+        Ok(token)
+    }
+
+    // returns the authorization header value, reissuing the token first if it is stale.
+    // `rejected` is the header value KIS refused, which forces a reissue unless another
+    // request already replaced it.
+    async fn authorization(&self, rejected: Option<&str>) -> Result<String> {
+        {
+            let token = self.token.read().await;
+            let authorization = token.authorization();
+            if !token.is_expired() && rejected != Some(authorization.as_str()) {
+                return Ok(authorization);
+            }
+        }
+
+        let mut token = self.token.write().await;
+        let authorization = token.authorization();
+        if !token.is_expired() && rejected != Some(authorization.as_str()) {
+            return Ok(authorization);
+        }
+        *token = self.issue_token(Some(&authorization)).await?;
+        Ok(token.authorization())
     }
 
     async fn request_token(&self) -> Result<Token> {
@@ -114,11 +255,41 @@ impl Session {
         let response = self.client
             .post(url)
             .json(&body)
-            .send().await.expect("request send failed");
-        let res : Token = response.json().await?;
+            .send().await?;
+        let res : Token = decode_auth(response).await?;
         Ok(res)
     }
 
+    // revokes the access token and drops it from the token store; the session must not be
+    // used afterwards. A token loaded from the store may belong to another process sharing
+    // it (a live runner next to a backtest), so only a token this session issued is revoked.
+    pub async fn close(&self) -> Result<()> {
+        if self.cassette.as_ref().is_some_and(Cassette::is_replay) {
+            return Ok(());
+        }
+
+        let mut token = self.token.write().await;
+        let issued = !token.access_token().is_empty()
+            && *self.issued_token.lock().unwrap() == token.access_token();
+        if issued {
+            let url = format!("{}/oauth2/revokeP", self.domain);
+            let body = json!({
+                "appkey": self.app_key,
+                "appsecret": self.app_secret,
+                "token": token.access_token(),
+            });
+
+            let response = self.client
+                .post(url)
+                .json(&body)
+                .send().await?;
+            let _ : serde_json::Value = decode_auth(response).await?;
+            self.token_store.remove(&self.app_key).await?;
+        }
+        *token = Token::default();
+        Ok(())
+    }
+
     pub async fn request_ws_key(&self) -> Result<WsKey> {
         let url = format!("{}/oauth2/Approval", self.domain);
         let body = json!({
@@ -130,15 +301,110 @@ impl Session {
         let response = self.client
             .post(url)
             .json(&body)
-            .send().await.expect("request send failed");
-        let res : WsKey = response.json().await?;
+            .send().await?;
+        let res : WsKey = decode_auth(response).await?;
         Ok(res)
     }
 
-    async fn __fetch(&self, path: &str, tr_id: &str, sender: &Sender, body: &Option<serde_json::Value>) -> Result<serde_json::Value> {
+    pub async fn request_hashkey(&self, body: &serde_json::Value) -> Result<String> {
+        let url = format!("{}/uapi/hashkey", self.domain);
+        self.rate_limiter.acquire().await;
+        let response = self.client
+            .post(url)
+            .headers(self.header.clone())
+            .json(body)
+            .send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(Error::Status { status: status.as_u16(), body: text });
+        }
+        let res: serde_json::Value = serde_json::from_str(&text)?;
+        match res["HASH"].as_str() {
+            Some(hash) => Ok(hash.to_string()),
+            None => Err(Error::Decode(serde::de::Error::missing_field("HASH"))),
+        }
+    }
+
+    // returns the decoded body and the tr_cont response header
+    async fn __fetch(&self, path: &str, tr_id: &str, sender: &Sender, body: &Option<serde_json::Value>, tr_cont: &str) -> Result<(serde_json::Value, String)> {
+        let replay = self.cassette.as_ref().is_some_and(|c| c.is_replay());
+        let authorization = if replay {
+            String::new()
+        } else {
+            self.authorization(None).await?
+        };
+        let res = self.__fetch_once(&authorization, path, tr_id, sender, body, tr_cont).await;
+        let msg_cd = match &res {
+            Ok((value, _)) => value["msg_cd"].as_str(),
+            Err(e) => e.msg_cd(),
+        };
+        if !msg_cd.is_some_and(|cd| TOKEN_ERROR_CODES.contains(&cd)) {
+            return res;
+        }
+
+        // token was revoked or expired early: reissue and replay once.
+        // a replayed session has no token, the recording holds the second response next
+        let authorization = if replay {
+            authorization
+        } else {
+            self.authorization(Some(&authorization)).await?
+        };
+        self.__fetch_once(&authorization, path, tr_id, sender, body, tr_cont).await
+    }
+
+    async fn __fetch_once(&self, authorization: &str, path: &str, tr_id: &str, sender: &Sender, body: &Option<serde_json::Value>, tr_cont: &str) -> Result<(serde_json::Value, String)> {
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replay()) {
+            let interaction = cassette.replay_interaction(path, &self.environment.tr_id(tr_id), body).await?;
+            return Ok((decode_reply(interaction.status, interaction.response)?, interaction.tr_cont));
+        }
+        self.__fetch_with(authorization, path, tr_id, sender, body, tr_cont).await
+    }
+
+    async fn __fetch_with(&self, authorization: &str, path: &str, tr_id: &str, sender: &Sender, body: &Option<serde_json::Value>, tr_cont: &str) -> Result<(serde_json::Value, String)> {
+        let span = tracing::info_span!("kis_request",
+            tr_id = %self.environment.tr_id(tr_id), path, tr_cont,
+            status = field::Empty, rt_cd = field::Empty, msg_cd = field::Empty, latency_ms = field::Empty);
+        let started = Instant::now();
+        let res = self.__send(authorization, path, tr_id, sender, body, tr_cont)
+            .instrument(span.clone())
+            .await;
+
+        span.record("latency_ms", started.elapsed().as_millis() as u64);
+        let _enter = span.enter();
+        match &res {
+            Ok((value, _)) => {
+                span.record("rt_cd", value["rt_cd"].as_str().unwrap_or_default());
+                span.record("msg_cd", value["msg_cd"].as_str().unwrap_or_default());
+                tracing::info!("request done");
+            },
+            Err(Error::Api { rt_cd, msg_cd, msg }) => {
+                span.record("rt_cd", rt_cd.as_str());
+                span.record("msg_cd", msg_cd.as_str());
+                tracing::warn!(msg = msg.as_str(), "request rejected");
+            },
+            Err(e) => tracing::warn!(error = %e, "request failed"),
+        }
+        res
+    }
+
+    async fn __send(&self, authorization: &str, path: &str, tr_id: &str, sender: &Sender, body: &Option<serde_json::Value>, tr_cont: &str) -> Result<(serde_json::Value, String)> {
         let url = format!("{}{}", self.domain, path);
         let mut header = self.header.clone();
-        header.insert("tr_id", HeaderValue::from_str(tr_id).unwrap());
+        header.insert("authorization", header_value(authorization)?);
+        header.insert("tr_id", header_value(&self.environment.tr_id(tr_id))?);
+        if !tr_cont.is_empty() {
+            header.insert("tr_cont", header_value(tr_cont)?);
+        }
+        if let (Sender::POST, Some(body), true) = (sender, body, self.use_hashkey) {
+            header.insert("hashkey", header_value(&self.request_hashkey(body).await?)?);
+        }
+        let waited = self.rate_limiter.acquire().await;
+        tracing::debug!(
+            headers = ?redact::headers(&header),
+            body = %body.as_ref().map(redact::value).unwrap_or_default(),
+            waited_ms = waited.as_millis() as u64,
+            "sending");
         let request = match sender {
             Sender::POST => {
                 let request = self.client
@@ -162,26 +428,141 @@ impl Session {
             }
         };
 
-        let response = request.send().await.expect("request send failed");
-        let result = response.json().await?;
-        Ok(result)
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        tracing::Span::current().record("status", status);
+        let next = response.headers().get("tr_cont")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let text = response.text().await?;
+        if let Some(cassette) = &self.cassette {
+            cassette.record_interaction(Interaction {
+                path: path.to_string(),
+                tr_id: self.environment.tr_id(tr_id).into_owned(),
+                body: body.clone(),
+                status,
+                response: text.clone(),
+                tr_cont: next.clone(),
+            }).await?;
+        }
+        Ok((decode_reply(status, text)?, next))
+    }
+
+    pub async fn execute(&self, command: &dyn ApiCommand) -> Result<Response> {
+        self.execute_page(command, &command.body(), "").await
     }
 
-    pub async fn execute(&self, command: &Box<dyn ApiCommand>) -> Result<serde_json::Value> {
-        let res = self.__fetch(command.path(), command.tr_id(), command.sender(), &command.body()).await.expect("__fetch failed");
+    // follows tr_cont / CTX_AREA_* continuation keys until the last page and merges
+    // every page's output1 rows into one response
+    pub async fn execute_all(&self, command: &dyn ApiCommand) -> Result<Response> {
+        let mut body = command.body();
+        let mut res = self.execute_page(command, &body, "").await?;
+        let mut pages = 1;
+        while res.has_next() && pages < MAX_PAGES {
+            if let Some(serde_json::Value::Object(fields)) = &mut body {
+                res.continue_body(fields);
+            }
+            let next = self.execute_page(command, &body, "N").await?;
+            res.merge(next);
+            pages += 1;
+        }
+        if res.has_next() {
+            tracing::warn!(path = command.path(), pages, "continuation keys left after page limit");
+            return Err(Error::Truncated { pages });
+        }
         Ok(res)
     }
 
-    pub async fn execute_vec(&self, commands: &Vec<Box<dyn ApiCommand>>) -> Result<Vec<serde_json::Value>> {
-        let mut results = Vec::<serde_json::Value>::with_capacity(commands.len());
+    async fn execute_page(&self, command: &dyn ApiCommand, body: &Option<serde_json::Value>, tr_cont: &str) -> Result<Response> {
+        command.validate()?;
+        let mut attempt = 1;
+        loop {
+            let res = self.__fetch(command.path(), command.tr_id(), command.sender(), body, tr_cont).await
+                .and_then(|(value, next)| {
+                    let mut response = Response::try_from(value)?;
+                    response.tr_cont = next;
+                    Ok(response)
+                });
+            match res {
+                Err(e) if attempt < self.retry_policy.max_attempts
+                    && self.retry_policy.is_retryable(&e, command.idempotent()) => {
+                    tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+                    attempt += 1;
+                },
+                res => return res,
+            }
+        }
+    }
+
+    pub async fn execute_typed<C: TypedCommand>(&self, command: &C) -> Result<C::Output> {
+        let res = self.execute(command).await?;
+        C::parse(res)
+    }
+
+    // execute_all for paginated commands with a typed model
+    pub async fn execute_all_typed<C: TypedCommand>(&self, command: &C) -> Result<C::Output> {
+        let res = self.execute_all(command).await?;
+        C::parse(res)
+    }
+
+    pub async fn execute_vec(&self, commands: &[Box<dyn ApiCommand>]) -> Result<Vec<Response>> {
+        let mut results = Vec::<Response>::with_capacity(commands.len());
         for command in commands {
-            let res = self.execute(command).await.expect("execute failed");
+            let res = self.execute(command.as_ref()).await?;
             results.push(res);
         }
         Ok(results)
     }
+
+    // runs up to `limit` commands at once; results keep the order of `commands`
+    pub async fn execute_concurrent(&self, commands: &[Box<dyn ApiCommand>], limit: usize) -> Vec<Result<Response>> {
+        stream::iter(commands)
+            .map(|command| self.execute(command.as_ref()))
+            .buffered(limit.max(1))
+            .collect()
+            .await
+    }
+}
+
+fn decode_reply(status: u16, text: String) -> Result<serde_json::Value> {
+    if !(200..300).contains(&status) {
+        // KIS reports some business errors (e.g. expired token) with a 5xx and an envelope
+        if let Ok(response) = serde_json::from_str::<Response>(&text) {
+            if !response.rt_cd.is_empty() {
+                response.into_result()?;
+            }
+        }
+        return Err(Error::Status { status, body: text });
+    }
+    let result = serde_json::from_str(&text)?;
+    Ok(result)
+}
+
+fn header_value(value: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(value)
+        .map_err(|e| Error::Config(format!("invalid header value: {}", e)))
+}
+
+// oauth endpoints answer failures with an error_description body instead of rt_cd
+async fn decode_auth<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    let status = response.status();
+    let text = response.text().await?;
+    if !status.is_success() {
+        return Err(Error::Auth(format!("http status {}: {}", status.as_u16(), auth_body(&text))));
+    }
+    serde_json::from_str(&text).map_err(|e| Error::Auth(format!("unexpected response {}: {}", auth_body(&text), e)))
+}
+
+// token responses carry credentials, so errors only keep a redacted copy
+fn auth_body(text: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(value) => redact::value(&value).to_string(),
+        Err(_) => format!("({} bytes)", text.len()),
+    }
 }
 
+#[allow(dead_code)]
 pub struct WsSession {
     key : WsKey,
     domain : String,
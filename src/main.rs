@@ -1,30 +1,76 @@
 use dotenv::dotenv;
+use std::collections::HashMap;
 use std::env;
-use trading::{Session, WsSession, WsKey};
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+use trading::{Session, WsSession};
+use trading::config::Config;
+use trading::database::DataBase;
 use trading::time_runner::TimeRunner;
-use trading::strategy::*;
+use trading::registry::SessionRegistry;
+use trading::token_store::NamespacedFileTokenStore;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let app_key = env::var("APP_KEY").expect("APP_KEY must be set.");
-    let app_secret = env::var("APP_SECRET").expect("APP_SECRET must be set.");
-    let domain = env::var("DOMAIN").expect("DOMAIN must be set");
-    let session = Session::new(app_key, app_secret, domain).await
-        .expect("create session failed");
-    let ws_domain = env::var("WS_DOMAIN").expect("WS_DOMAIN must be set");
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+    let config_path = env::var("CONFIG").unwrap_or("config.toml".to_string());
+    let config = Config::load(&config_path)
+        .unwrap_or_else(|e| panic!("invalid configuration: {}", e));
+
+    // accounts under the same app key share one session
+    let mut sessions: HashMap<String, Arc<Session>> = HashMap::new();
+    let mut registry = SessionRegistry::new();
+    for account in &config.accounts {
+        let session = match sessions.get(&account.app_key) {
+            Some(session) => session.clone(),
+            None => {
+                let mut builder = Session::builder_for(account.app_key.clone(), account.app_secret.clone(), config.environment())
+                    .token_store(NamespacedFileTokenStore::new(&config.token_dir));
+                if let Some(domain) = &config.domain {
+                    builder = builder.domain(domain.clone());
+                }
+                let session = Arc::new(builder.build().await
+                    .expect("create session failed"));
+                sessions.insert(account.app_key.clone(), session.clone());
+                session
+            },
+        };
+        registry.register(account.name.clone(), account.to_account(), session);
+    }
+
+    let session = &registry.accounts().next().unwrap().session;
+    let ws_domain = env::var("WS_DOMAIN").unwrap_or(session.ws_domain().to_string());
     let ws_key = session.request_ws_key().await
         .expect("Failed to get approval_key");
-    let ws_session = WsSession::new(ws_key, ws_domain).await
+    let _ws_session = WsSession::new(ws_key, ws_domain).await
         .expect("create ws_session failed");
-    let mut runner = TimeRunner::new(session);
-
-    let account_no = env::var("ACCOUNT_NO").expect("ACCOUNT_NO must be set");
-    let account_cd = env::var("ACCOUNT_CD").expect("ACCOUNT_CD must be set");
 
-    let account = Account::new(account_no, account_cd, 2500000);
+    let strategies = config.strategies.iter()
+        .map(|s| s.to_strategy(config.account(&s.account).unwrap().to_account()))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("invalid strategy");
 
-    let strategies = vec![Strategy::Test(account.clone()), Strategy::PriceMomentum(account.clone())];
+    let mut runner = TimeRunner::with_database(registry, DataBase::from_dir(&config.data_dir));
+    let (start, end) = (config.backtest.start, config.backtest.end);
+    let back_test = tokio::task::spawn_blocking(move || runner.run_back_test(start, end, strategies));
 
-    runner.run_back_test(20230101, 20230512, strategies);
+    // close on Ctrl-C as well, so an interrupted run does not leave its token valid
+    let interrupted = tokio::select! {
+        res = back_test => {
+            res.expect("back test failed");
+            false
+        },
+        _ = tokio::signal::ctrl_c() => true,
+    };
+    for session in sessions.values() {
+        session.close().await
+            .expect("close session failed");
+    }
+    if interrupted {
+        // the back test thread cannot be cancelled, don't wait for it
+        std::process::exit(130);
+    }
 }
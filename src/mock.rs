@@ -0,0 +1,363 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::ops::Add;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{Utc, FixedOffset};
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use crate::Result;
+
+// in-process stand-in for the KIS REST server, for tests that must not touch the network
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method : String,
+    pub path : String,
+    pub tr_id : Option<String>,
+    pub headers : HashMap<String, String>,
+    pub query : HashMap<String, String>,
+    pub body : Option<serde_json::Value>,
+}
+
+impl MockRequest {
+    // a query parameter or top-level body field
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(String::as_str)
+            .or_else(|| self.body.as_ref().and_then(|b| b[name].as_str()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status : u16,
+    pub body : serde_json::Value,
+    pub headers : Vec<(String, String)>,
+    pub delay : Duration,
+    // close the connection without answering, like a dropped link
+    pub disconnect : bool,
+}
+
+impl MockResponse {
+    pub fn ok(body: serde_json::Value) -> Self {
+        MockResponse {
+            status: 200,
+            body,
+            headers: Vec::new(),
+            delay: Duration::ZERO,
+            disconnect: false,
+        }
+    }
+
+    // successful envelope around `output`
+    pub fn output(output: serde_json::Value) -> Self {
+        Self::ok(json!({
+            "rt_cd": "0",
+            "msg_cd": "MCA00000",
+            "msg1": "정상처리 되었습니다.",
+            "output": output,
+        }))
+    }
+
+    // business error the way KIS reports it
+    pub fn api_error(msg_cd: &str, msg: &str) -> Self {
+        Self::ok(json!({
+            "rt_cd": "1",
+            "msg_cd": msg_cd,
+            "msg1": msg,
+        })).status(500)
+    }
+
+    pub fn disconnect() -> Self {
+        MockResponse {
+            disconnect: true,
+            ..Self::ok(serde_json::Value::Null)
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    scripted : HashMap<String, VecDeque<MockResponse>>,
+    // (path, field, value, response), served before `scripted`
+    matching : Vec<(String, String, String, MockResponse)>,
+    requests : Vec<MockRequest>,
+    issued_tokens : u32,
+}
+
+pub struct MockServer {
+    addr : SocketAddr,
+    state : Arc<Mutex<MockState>>,
+    handle : JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let handle = tokio::spawn(serve(listener, state.clone()));
+
+        Ok(MockServer {
+            addr,
+            state,
+            handle,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // queue a response for the next request to `path`; queued responses are served
+    // in order before falling back to the built-in default
+    pub fn push(&self, path: &str, response: MockResponse) {
+        self.state.lock().unwrap().scripted
+            .entry(path.to_string())
+            .or_default()
+            .push_back(response);
+    }
+
+    // queue a response for the next request to `path` whose query or body has
+    // `field` = `value`, so concurrent requests get their own answers
+    pub fn push_matching(&self, path: &str, field: &str, value: &str, response: MockResponse) {
+        self.state.lock().unwrap().matching
+            .push((path.to_string(), field.to_string(), value.to_string(), response));
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn requests_to(&self, path: &str) -> Vec<MockRequest> {
+        self.requests().into_iter().filter(|r| r.path == path).collect()
+    }
+
+    pub fn issued_tokens(&self) -> u32 {
+        self.state.lock().unwrap().issued_tokens
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<Mutex<MockState>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(handle_connection(stream, state.clone()));
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let mut reader = BufReader::new(stream);
+    let request = match read_request(&mut reader).await {
+        Some(request) => request,
+        None => return,
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        let matched = state.matching.iter()
+            .position(|(path, field, value, _)| path == &request.path && request.field(field) == Some(value.as_str()));
+        let scripted = match matched {
+            Some(i) => Some(state.matching.remove(i).3),
+            None => state.scripted.get_mut(&request.path).and_then(VecDeque::pop_front),
+        };
+        scripted.unwrap_or_else(|| default_response(&mut state, &request))
+    };
+
+    if !response.delay.is_zero() {
+        tokio::time::sleep(response.delay).await;
+    }
+    if response.disconnect {
+        return;
+    }
+
+    let body = response.body.to_string();
+    let mut head = format!(
+        "HTTP/1.1 {} MOCK\r\ncontent-type: application/json; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n",
+        response.status, body.len());
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let mut stream = reader.into_inner();
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(body.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(reader: &mut BufReader<TcpStream>) -> Option<MockRequest> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await.ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers.get("content-length").and_then(|l| l.parse::<usize>().ok()).unwrap_or(0);
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).await.ok()?;
+    let body = serde_json::from_slice(&body).ok();
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), parse_query(query)),
+        None => (target, HashMap::new()),
+    };
+
+    Some(MockRequest {
+        method,
+        path,
+        tr_id: headers.get("tr_id").cloned(),
+        headers,
+        query,
+        body,
+    })
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (percent_decode(k), percent_decode(v)))
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = match bytes[i] {
+            b'%' if i + 2 < bytes.len() => s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            },
+            None => {
+                out.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn default_response(state: &mut MockState, request: &MockRequest) -> MockResponse {
+    match request.path.as_str() {
+        "/oauth2/tokenP" => {
+            state.issued_tokens += 1;
+            let kst = FixedOffset::east_opt(3600 * 9).unwrap();
+            let expired = Utc::now().naive_utc().add(kst) + chrono::Duration::days(1);
+            MockResponse::ok(json!({
+                "access_token": format!("mock-token-{}", state.issued_tokens),
+                "access_token_token_expired": expired.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "token_type": "Bearer",
+                "expires_in": 86400,
+            }))
+        },
+        "/oauth2/Approval" => MockResponse::ok(json!({ "approval_key": "mock-approval-key" })),
+        "/oauth2/revokeP" => MockResponse::ok(json!({ "code": 200, "message": "접근토큰 폐기에 성공하였습니다" })),
+        "/uapi/hashkey" => MockResponse::ok(json!({
+            "BODY": request.body.clone().unwrap_or_default(),
+            "HASH": "mock-hash",
+        })),
+        "/uapi/domestic-stock/v1/quotations/inquire-price" => MockResponse::output(json!({
+            "stck_prpr": "70000",
+            "prdy_vrss": "500",
+            "prdy_ctrt": "0.72",
+            "acml_vol": "1000000",
+            "stck_oprc": "69500",
+            "stck_hgpr": "70500",
+            "stck_lwpr": "69000",
+        })),
+        "/uapi/domestic-stock/v1/quotations/inquire-daily-price" => MockResponse::output(json!([])),
+        "/uapi/domestic-stock/v1/quotations/inquire-asking-price-exp-ccn" => {
+            let mut book = serde_json::Map::new();
+            for i in 1..=10 {
+                book.insert(format!("askp{}", i), json!((70000 + 100 * i).to_string()));
+                book.insert(format!("bidp{}", i), json!((70100 - 100 * i).to_string()));
+                book.insert(format!("askp_rsqn{}", i), json!((1000 * i).to_string()));
+                book.insert(format!("bidp_rsqn{}", i), json!((1500 * i).to_string()));
+            }
+            book.insert("total_askp_rsqn".to_string(), json!("55000"));
+            book.insert("total_bidp_rsqn".to_string(), json!("82500"));
+            book.insert("aspr_acpt_hour".to_string(), json!("121052"));
+            MockResponse::ok(json!({
+                "rt_cd": "0",
+                "msg_cd": "MCA00000",
+                "msg1": "정상처리 되었습니다.",
+                "output1": book,
+                "output2": { "antc_cnpr": "0", "antc_cnqn": "0", "stck_prpr": "70000", "stck_sdpr": "69500" },
+            }))
+        },
+        "/uapi/domestic-stock/v1/trading/inquire-balance" => MockResponse::ok(json!({
+            "rt_cd": "0",
+            "msg_cd": "KIOK0510",
+            "msg1": "조회가 완료되었습니다",
+            "output1": [],
+            "output2": [{ "dnca_tot_amt": "2500000", "tot_evlu_amt": "2500000", "nass_amt": "2500000" }],
+        })),
+        "/uapi/domestic-stock/v1/trading/inquire-daily-ccld" => MockResponse::ok(json!({
+            "rt_cd": "0",
+            "msg_cd": "KIOK0510",
+            "msg1": "조회가 완료되었습니다",
+            "output1": [],
+            "output2": { "tot_ord_qty": "0", "tot_ccld_qty": "0", "tot_ccld_amt": "0" },
+        })),
+        "/uapi/domestic-stock/v1/trading/inquire-psbl-order" => MockResponse::output(json!({
+            "ord_psbl_cash": "2500000",
+            "psbl_qty_calc_unpr": "70000",
+            "nrcvb_buy_amt": "2500000",
+            "nrcvb_buy_qty": "35",
+            "max_buy_amt": "2500000",
+            "max_buy_qty": "35",
+        })),
+        "/uapi/domestic-stock/v1/trading/order-cash" => MockResponse::output(json!({
+            "KRX_FWDG_ORD_ORGNO": "91252",
+            "ODNO": "0000117057",
+            "ORD_TMD": "121052",
+        })),
+        "/uapi/domestic-stock/v1/trading/order-rvsecncl" => MockResponse::output(json!({
+            "KRX_FWDG_ORD_ORGNO": "91252",
+            "ODNO": "0000117058",
+            "ORD_TMD": "121105",
+        })),
+        _ => MockResponse::ok(json!({ "rt_cd": "1", "msg_cd": "MOCK404", "msg1": "no mock for path" })).status(404),
+    }
+}
@@ -0,0 +1,410 @@
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+use crate::database::Column;
+use crate::strategy::Account;
+
+// typed views of KIS responses. KIS encodes every number as a string ("70000", "-0.72",
+// sometimes padded or empty), so numeric fields go through `number`.
+
+// KIS numbers arrive as strings; blanks become the default value
+pub(crate) fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + Default,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let text = match &value {
+        serde_json::Value::String(s) => s.trim().to_string(),
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::Null => String::new(),
+        v => return Err(serde::de::Error::custom(format!("expected a number, got {}", v))),
+    };
+    if text.is_empty() {
+        return Ok(T::default());
+    }
+    if let Ok(v) = text.parse::<T>() {
+        return Ok(v);
+    }
+    // integer fields occasionally carry a fractional part ("70000.00")
+    text.parse::<f64>().ok()
+        .and_then(|f| f.trunc().to_string().parse::<T>().ok())
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid number {}", text)))
+}
+
+// inquire-price (FHKST01010100) output
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct PriceResponse {
+    // 현재가
+    #[serde(rename = "stck_prpr", deserialize_with = "number")]
+    pub price : i64,
+    // 전일 대비
+    #[serde(rename = "prdy_vrss", deserialize_with = "number")]
+    pub change : i64,
+    // 전일 대비율 (%)
+    #[serde(rename = "prdy_ctrt", deserialize_with = "number")]
+    pub change_rate : f64,
+    // 누적 거래량
+    #[serde(rename = "acml_vol", deserialize_with = "number")]
+    pub volume : u64,
+    // 누적 거래 대금
+    #[serde(rename = "acml_tr_pbmn", deserialize_with = "number")]
+    pub amount : u64,
+    #[serde(rename = "stck_oprc", deserialize_with = "number")]
+    pub open : i64,
+    #[serde(rename = "stck_hgpr", deserialize_with = "number")]
+    pub high : i64,
+    #[serde(rename = "stck_lwpr", deserialize_with = "number")]
+    pub low : i64,
+    // 상한가 / 하한가
+    #[serde(rename = "stck_mxpr", deserialize_with = "number")]
+    pub upper_limit : i64,
+    #[serde(rename = "stck_llam", deserialize_with = "number")]
+    pub lower_limit : i64,
+    // 기준가 (전일 종가)
+    #[serde(rename = "stck_sdpr", deserialize_with = "number")]
+    pub base_price : i64,
+    #[serde(deserialize_with = "number")]
+    pub per : f64,
+    #[serde(deserialize_with = "number")]
+    pub pbr : f64,
+    #[serde(deserialize_with = "number")]
+    pub eps : f64,
+    #[serde(deserialize_with = "number")]
+    pub bps : f64,
+    // HTS 시가총액 (억원)
+    #[serde(rename = "hts_avls", deserialize_with = "number")]
+    pub market_cap : u64,
+}
+
+// inquire-daily-price (FHKST01010400) output row
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct DailyPriceRow {
+    // 영업 일자 (YYYYMMDD)
+    #[serde(rename = "stck_bsop_date")]
+    pub date : String,
+    #[serde(rename = "stck_oprc", deserialize_with = "number")]
+    pub open : i32,
+    #[serde(rename = "stck_hgpr", deserialize_with = "number")]
+    pub high : i32,
+    #[serde(rename = "stck_lwpr", deserialize_with = "number")]
+    pub low : i32,
+    #[serde(rename = "stck_clpr", deserialize_with = "number")]
+    pub close : i32,
+    #[serde(rename = "acml_vol", deserialize_with = "number")]
+    pub volume : u32,
+    #[serde(rename = "prdy_vrss", deserialize_with = "number")]
+    pub change : i32,
+    #[serde(rename = "prdy_ctrt", deserialize_with = "number")]
+    pub change_rate : f32,
+}
+
+// inquire-daily-itemchartprice (FHKST03010100) output2 row
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct DailyChartRow {
+    #[serde(rename = "stck_bsop_date")]
+    pub date : String,
+    #[serde(rename = "stck_oprc", deserialize_with = "number")]
+    pub open : i32,
+    #[serde(rename = "stck_hgpr", deserialize_with = "number")]
+    pub high : i32,
+    #[serde(rename = "stck_lwpr", deserialize_with = "number")]
+    pub low : i32,
+    #[serde(rename = "stck_clpr", deserialize_with = "number")]
+    pub close : i32,
+    #[serde(rename = "acml_vol", deserialize_with = "number")]
+    pub volume : u32,
+    // 누적 거래 대금
+    #[serde(rename = "acml_tr_pbmn", deserialize_with = "number")]
+    pub amount : u64,
+    #[serde(rename = "prdy_vrss", deserialize_with = "number")]
+    pub change : i32,
+}
+
+impl From<DailyPriceRow> for Column {
+    // this endpoint has no trading amount, so amount stays 0
+    fn from(row: DailyPriceRow) -> Self {
+        Column {
+            date: column_date(&row.date),
+            open_price: row.open,
+            high_price: row.high,
+            low_price: row.low,
+            close_price: row.close,
+            volume: row.volume,
+            amount: 0,
+            performance: row.change_rate,
+        }
+    }
+}
+
+impl From<DailyChartRow> for Column {
+    // the chart endpoint has no change rate, so it is derived from the previous close
+    fn from(row: DailyChartRow) -> Self {
+        let prev_close = row.close - row.change;
+        let performance = if prev_close == 0 {
+            0.0
+        } else {
+            (f64::from(row.change) / f64::from(prev_close) * 100.0) as f32
+        };
+        Column {
+            date: column_date(&row.date),
+            open_price: row.open,
+            high_price: row.high,
+            low_price: row.low,
+            close_price: row.close,
+            volume: row.volume,
+            amount: row.amount,
+            performance,
+        }
+    }
+}
+
+// KIS dates are YYYYMMDD, the CSV history uses YYYY-MM-DD
+fn column_date(date: &str) -> String {
+    match (date.get(0..4), date.get(4..6), date.get(6..8)) {
+        (Some(y), Some(m), Some(d)) if date.len() == 8 => format!("{}-{}-{}", y, m, d),
+        _ => date.to_string(),
+    }
+}
+
+// decodes a list payload (newest first, like DataBase columns) into rows of `T`
+pub(crate) fn rows<T: serde::de::DeserializeOwned>(payload: Option<serde_json::Value>) -> crate::Result<Vec<T>> {
+    match payload {
+        None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+        Some(payload) => Ok(serde_json::from_value(payload)?),
+    }
+}
+
+// inquire-balance (TTTC8434R) output1 row
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct Holding {
+    #[serde(rename = "pdno")]
+    pub ticker : String,
+    #[serde(rename = "prdt_name")]
+    pub name : String,
+    // 보유 수량 / 주문 가능 수량
+    #[serde(rename = "hldg_qty", deserialize_with = "number")]
+    pub quantity : u32,
+    #[serde(rename = "ord_psbl_qty", deserialize_with = "number")]
+    pub orderable_quantity : u32,
+    // 매입 평균 가격
+    #[serde(rename = "pchs_avg_pric", deserialize_with = "number")]
+    pub avg_price : f64,
+    #[serde(rename = "pchs_amt", deserialize_with = "number")]
+    pub purchase_amount : i64,
+    #[serde(rename = "prpr", deserialize_with = "number")]
+    pub price : i64,
+    // 평가 금액 / 평가 손익
+    #[serde(rename = "evlu_amt", deserialize_with = "number")]
+    pub eval_amount : i64,
+    #[serde(rename = "evlu_pfls_amt", deserialize_with = "number")]
+    pub profit : i64,
+    #[serde(rename = "evlu_pfls_rt", deserialize_with = "number")]
+    pub profit_rate : f64,
+}
+
+// inquire-balance output2 (single row)
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct BalanceSummary {
+    // 예수금 총금액
+    #[serde(rename = "dnca_tot_amt", deserialize_with = "number")]
+    pub deposit : i64,
+    // D+2 예수금, what is left after pending settlements
+    #[serde(rename = "prvs_rcdl_excc_amt", deserialize_with = "number")]
+    pub settled_deposit : i64,
+    // 유가 평가 금액
+    #[serde(rename = "scts_evlu_amt", deserialize_with = "number")]
+    pub stock_eval_amount : i64,
+    // 총 평가 금액
+    #[serde(rename = "tot_evlu_amt", deserialize_with = "number")]
+    pub total_eval_amount : i64,
+    // 순 자산 금액
+    #[serde(rename = "nass_amt", deserialize_with = "number")]
+    pub net_asset : i64,
+    #[serde(rename = "pchs_amt_smtl_amt", deserialize_with = "number")]
+    pub purchase_amount : i64,
+    #[serde(rename = "evlu_pfls_smtl_amt", deserialize_with = "number")]
+    pub profit : i64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BalanceResponse {
+    pub holdings : Vec<Holding>,
+    pub summary : BalanceSummary,
+}
+
+impl BalanceResponse {
+    // cash is the D+2 deposit so unsettled buys are not spent twice; an overdrawn (미수)
+    // account has nothing to spend. Fails when the deposit does not fit Account::amount.
+    pub fn to_account(&self, account_no: String, account_cd: String) -> crate::Result<Account> {
+        let deposit = self.summary.settled_deposit.max(0);
+        let cash = i32::try_from(deposit).map_err(|_| crate::Error::Decode(serde::de::Error::custom(
+            format!("deposit {} does not fit Account::amount", deposit))))?;
+        let mut account = Account::new(account_no, account_cd, cash);
+        for holding in self.holdings.iter().filter(|h| h.quantity > 0) {
+            account.stocks.insert(holding.ticker.clone(), holding.quantity);
+        }
+        Ok(account)
+    }
+}
+
+// order-cash / order-rvsecncl output
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct OrderResponse {
+    // 한국거래소 전송 주문 조직 번호, needed with order_no to modify or cancel
+    #[serde(rename = "KRX_FWDG_ORD_ORGNO")]
+    pub branch : String,
+    #[serde(rename = "ODNO")]
+    pub order_no : String,
+    // 주문 시각 (HHMMSS)
+    #[serde(rename = "ORD_TMD")]
+    pub time : String,
+}
+
+// inquire-daily-ccld (TTTC8001R) output1 row
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct Execution {
+    // 주문 일자 (YYYYMMDD) / 시각 (HHMMSS)
+    #[serde(rename = "ord_dt")]
+    pub date : String,
+    #[serde(rename = "ord_tmd")]
+    pub time : String,
+    #[serde(rename = "ord_gno_brno")]
+    pub branch : String,
+    #[serde(rename = "odno")]
+    pub order_no : String,
+    // set on modify/cancel orders
+    #[serde(rename = "orgn_odno")]
+    pub original_order_no : String,
+    // 01 sell, 02 buy
+    #[serde(rename = "sll_buy_dvsn_cd")]
+    pub side : String,
+    #[serde(rename = "pdno")]
+    pub ticker : String,
+    #[serde(rename = "prdt_name")]
+    pub name : String,
+    #[serde(rename = "ord_dvsn_name")]
+    pub order_type_name : String,
+    #[serde(rename = "ord_qty", deserialize_with = "number")]
+    pub quantity : u32,
+    #[serde(rename = "ord_unpr", deserialize_with = "number")]
+    pub price : i64,
+    // 총 체결 수량 / 평균가 / 체결 금액
+    #[serde(rename = "tot_ccld_qty", deserialize_with = "number")]
+    pub filled_quantity : u32,
+    #[serde(rename = "avg_prvs", deserialize_with = "number")]
+    pub avg_price : f64,
+    #[serde(rename = "tot_ccld_amt", deserialize_with = "number")]
+    pub filled_amount : i64,
+    // 잔여 수량 / 거부 수량
+    #[serde(rename = "rmn_qty", deserialize_with = "number")]
+    pub remaining_quantity : u32,
+    #[serde(rename = "rjct_qty", deserialize_with = "number")]
+    pub rejected_quantity : u32,
+    #[serde(rename = "cncl_yn")]
+    pub cancelled : String,
+}
+
+impl Execution {
+    pub fn is_buy(&self) -> bool {
+        self.side == "02"
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled == "Y"
+    }
+}
+
+// inquire-psbl-order (TTTC8908R) output
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct OrderableAmountResponse {
+    // 주문 가능 현금
+    #[serde(rename = "ord_psbl_cash", deserialize_with = "number")]
+    pub cash : i64,
+    // price the quantities were computed at (the current price for market orders)
+    #[serde(rename = "psbl_qty_calc_unpr", deserialize_with = "number")]
+    pub price : i64,
+    // 미수 없는 매수 금액 / 수량, what a cash account can actually buy
+    #[serde(rename = "nrcvb_buy_amt", deserialize_with = "number")]
+    pub buyable_amount : i64,
+    #[serde(rename = "nrcvb_buy_qty", deserialize_with = "number")]
+    pub buyable_quantity : u32,
+    // 최대 매수 금액 / 수량, including buying on receivables (미수)
+    #[serde(rename = "max_buy_amt", deserialize_with = "number")]
+    pub max_buy_amount : i64,
+    #[serde(rename = "max_buy_qty", deserialize_with = "number")]
+    pub max_buy_quantity : u32,
+}
+
+// one price level of the order book
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BookLevel {
+    pub price : i64,
+    pub quantity : u64,
+}
+
+// inquire-asking-price-exp-ccn (FHKST01010200), output1 (호가) and output2 (예상 체결)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AskingPriceResponse {
+    // best first; empty levels (price 0) are left out
+    pub asks : Vec<BookLevel>,
+    pub bids : Vec<BookLevel>,
+    pub total_ask_quantity : u64,
+    pub total_bid_quantity : u64,
+    // 호가 접수 시각 (HHMMSS)
+    pub time : String,
+    // 예상 체결가 / 수량, only set during the opening and closing auctions
+    pub expected_price : i64,
+    pub expected_quantity : u64,
+    pub price : i64,
+}
+
+impl AskingPriceResponse {
+    // the output1 fields are flat: askp1..askp10, askp_rsqn1..askp_rsqn10, ...
+    pub(crate) fn from_outputs(book: serde_json::Value, expected: serde_json::Value) -> crate::Result<Self> {
+        let field = |v: &serde_json::Value, key: &str| v.get(key).cloned().unwrap_or_default();
+        let level = |side: &str, i: usize| -> crate::Result<BookLevel> {
+            Ok(BookLevel {
+                price: number(field(&book, &format!("{}p{}", side, i)))?,
+                quantity: number(field(&book, &format!("{}p_rsqn{}", side, i)))?,
+            })
+        };
+        let mut asks = Vec::with_capacity(10);
+        let mut bids = Vec::with_capacity(10);
+        for i in 1..=10 {
+            asks.push(level("ask", i)?);
+            bids.push(level("bid", i)?);
+        }
+        asks.retain(|l| l.price > 0);
+        bids.retain(|l| l.price > 0);
+        Ok(AskingPriceResponse {
+            asks,
+            bids,
+            total_ask_quantity: number(field(&book, "total_askp_rsqn"))?,
+            total_bid_quantity: number(field(&book, "total_bidp_rsqn"))?,
+            time: field(&book, "aspr_acpt_hour").as_str().unwrap_or_default().to_string(),
+            expected_price: number(field(&expected, "antc_cnpr"))?,
+            expected_quantity: number(field(&expected, "antc_cnqn"))?,
+            price: number(field(&expected, "stck_prpr"))?,
+        })
+    }
+
+    pub fn best_ask(&self) -> Option<BookLevel> {
+        self.asks.first().copied()
+    }
+
+    pub fn best_bid(&self) -> Option<BookLevel> {
+        self.bids.first().copied()
+    }
+
+    pub fn spread(&self) -> Option<i64> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+}
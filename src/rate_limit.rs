@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+// KIS allows about 20 req/s on the real server and far fewer on the paper server.
+// Stay a little below so clock skew between us and the gateway doesn't trip EGW00201.
+const REAL_REQUESTS_PER_SEC: f64 = 18.0;
+const PAPER_REQUESTS_PER_SEC: f64 = 2.0;
+
+struct Bucket {
+    tokens : f64,
+    updated : Instant,
+}
+
+// token bucket shared by every request of a Session
+pub struct RateLimiter {
+    capacity : f64,
+    per_sec : f64,
+    bucket : Mutex<Bucket>,
+    throttled : AtomicU64,
+    waited_micros : AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RateLimitStats {
+    // number of requests that had to wait for a slot
+    pub throttled : u64,
+    // total time spent waiting
+    pub waited : Duration,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: f64, burst: u32) -> Self {
+        let capacity = f64::from(burst.max(1));
+        RateLimiter {
+            capacity,
+            per_sec,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+            throttled: AtomicU64::new(0),
+            waited_micros: AtomicU64::new(0),
+        }
+    }
+
+    // no burst: a full bucket of per_sec on top of the refill would let almost twice
+    // the limit through in the first second
+    pub fn real() -> Self {
+        Self::new(REAL_REQUESTS_PER_SEC, 1)
+    }
+
+    pub fn paper() -> Self {
+        Self::new(PAPER_REQUESTS_PER_SEC, 1)
+    }
+
+    // waits for a slot and returns how long it waited
+    pub async fn acquire(&self) -> Duration {
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.capacity);
+            bucket.updated = now;
+            // reserve the slot now so later callers queue behind us
+            bucket.tokens -= 1.0;
+            if bucket.tokens >= 0.0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64(-bucket.tokens / self.per_sec)
+            }
+        };
+
+        if !wait.is_zero() {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            self.waited_micros.fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            throttled: self.throttled.load(Ordering::Relaxed),
+            waited: Duration::from_micros(self.waited_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
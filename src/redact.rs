@@ -0,0 +1,53 @@
+use reqwest::header::HeaderMap;
+
+// credentials are hidden completely
+const SECRET_KEYS: [&str; 7] = ["appkey", "appsecret", "secretkey", "authorization", "token", "access_token", "approval_key"];
+// account numbers keep their last two digits so logs of different accounts can be told apart
+const ACCOUNT_KEYS: [&str; 1] = ["CANO"];
+
+const MASK: &str = "***";
+
+pub fn value(v: &serde_json::Value) -> serde_json::Value {
+    match v {
+        serde_json::Value::Object(fields) => fields.iter()
+            .map(|(k, v)| (k.clone(), field(k, v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        serde_json::Value::Array(rows) => rows.iter().map(value).collect(),
+        _ => v.clone(),
+    }
+}
+
+pub fn headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers.iter()
+        .map(|(k, v)| {
+            let v = if is_secret(k.as_str()) {
+                MASK.to_string()
+            } else {
+                v.to_str().unwrap_or(MASK).to_string()
+            };
+            (k.to_string(), v)
+        })
+        .collect()
+}
+
+fn field(key: &str, v: &serde_json::Value) -> serde_json::Value {
+    if is_secret(key) {
+        return MASK.into();
+    }
+    if ACCOUNT_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+        return account(v.as_str().unwrap_or_default()).into();
+    }
+    value(v)
+}
+
+fn is_secret(key: &str) -> bool {
+    SECRET_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key))
+}
+
+fn account(account_no: &str) -> String {
+    let chars: Vec<char> = account_no.chars().collect();
+    let keep = chars.len().saturating_sub(2);
+    let tail: String = chars[keep..].iter().collect();
+    format!("{}{}", "*".repeat(keep), tail)
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use crate::command::*;
+use crate::strategy::Account;
+use crate::model::BalanceResponse;
+use crate::{Session, Result};
+
+// an account and the session (app key) that trades it
+pub struct AccountSession {
+    pub name : String,
+    pub account : Account,
+    pub session : Arc<Session>,
+}
+
+// every account this process trades, so strategies can be bound to one of them
+#[derive(Default)]
+pub struct SessionRegistry {
+    entries : Vec<AccountSession>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // accounts under the same app key may share one session
+    pub fn register(&mut self, name: String, account: Account, session: Arc<Session>) {
+        self.entries.retain(|e| e.name != name);
+        self.entries.push(AccountSession {
+            name,
+            account,
+            session,
+        });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AccountSession> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    pub fn session_for(&self, account: &Account) -> Option<&Session> {
+        self.entries.iter()
+            .find(|e| e.account.account_no == account.account_no && e.account.account_cd == account.account_cd)
+            .map(|e| e.session.as_ref())
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = &AccountSession> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // balance inquiry of every account, by account name
+    pub async fn balances(&self) -> Vec<(String, Result<BalanceResponse>)> {
+        let mut results = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let balance_cmd = Command::<Balance>::new()
+                .account_no(entry.account.account_no.clone())
+                .account_cd(entry.account.account_cd.clone());
+            let res = entry.session.execute_all_typed(&balance_cmd).await;
+            results.push((entry.name.clone(), res));
+        }
+        results
+    }
+
+    // net asset summed over every account
+    pub async fn total_net_asset(&self) -> Result<i64> {
+        let mut total = 0;
+        for (_, res) in self.balances().await {
+            total += res?.summary.net_asset;
+        }
+        Ok(total)
+    }
+
+    // closes each distinct session once
+    pub async fn close(&self) -> Result<()> {
+        let mut closed: Vec<&Arc<Session>> = Vec::new();
+        for entry in &self.entries {
+            if closed.iter().any(|s| Arc::ptr_eq(s, &entry.session)) {
+                continue;
+            }
+            entry.session.close().await?;
+            closed.push(&entry.session);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Serialize, Deserialize};
+use crate::{Error, Result};
+
+// common envelope of every KIS REST reply
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Response {
+    pub rt_cd : String,
+    pub msg_cd : String,
+    pub msg1 : String,
+    pub output : Option<serde_json::Value>,
+    pub output1 : Option<serde_json::Value>,
+    pub output2 : Option<serde_json::Value>,
+    // tr_cont response header: F/M when more pages follow
+    #[serde(skip)]
+    pub tr_cont : String,
+    // continuation keys (ctx_area_fk100 ...) and other endpoint specific fields
+    #[serde(flatten)]
+    pub extra : serde_json::Map<String, serde_json::Value>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        self.rt_cd == "0"
+    }
+
+    pub fn has_next(&self) -> bool {
+        matches!(self.tr_cont.as_str(), "F" | "M")
+    }
+
+    // copy this page's continuation keys into the request body for the next page
+    pub(crate) fn continue_body(&self, body: &mut serde_json::Map<String, serde_json::Value>) {
+        for (key, value) in body.iter_mut() {
+            if key.to_ascii_uppercase().starts_with("CTX_AREA_") {
+                let next = self.extra.get(&key.to_ascii_lowercase())
+                    .or_else(|| self.extra.get(&key.to_ascii_uppercase()));
+                if let Some(next) = next {
+                    *value = serde_json::Value::String(next.as_str().unwrap_or_default().trim().to_string());
+                }
+            }
+        }
+    }
+
+    // append the rows of the next page; summaries (output2) are taken from the latest page
+    pub(crate) fn merge(&mut self, next: Response) {
+        append_rows(&mut self.output, next.output);
+        append_rows(&mut self.output1, next.output1);
+        if next.output2.is_some() {
+            self.output2 = next.output2;
+        }
+        self.tr_cont = next.tr_cont;
+        self.extra = next.extra;
+    }
+
+    // turn a non-zero rt_cd into Error::Api
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            Err(Error::Api {
+                rt_cd: self.rt_cd,
+                msg_cd: self.msg_cd,
+                msg: self.msg1.trim().to_string(),
+            })
+        }
+    }
+}
+
+impl TryFrom<serde_json::Value> for Response {
+    type Error = Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self> {
+        let response: Response = serde_json::from_value(value)?;
+        response.into_result()
+    }
+}
+
+fn append_rows(rows: &mut Option<serde_json::Value>, next: Option<serde_json::Value>) {
+    match (rows, next) {
+        (Some(serde_json::Value::Array(rows)), Some(serde_json::Value::Array(next))) => rows.extend(next),
+        (rows, Some(next)) => *rows = Some(next),
+        (_, None) => {},
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+use rand::Rng;
+use crate::Error;
+
+// msg_cd for "too many requests per second"; the gateway rejects before the request runs
+const RATE_LIMITED_MSG_CD: &str = "EGW00201";
+
+pub struct RetryPolicy {
+    pub(crate) max_attempts : u32,
+    base_delay : Duration,
+    max_delay : Duration,
+    jitter : bool,
+    retryable_msg_cds : Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            retryable_msg_cds: vec![RATE_LIMITED_MSG_CD.to_string()],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // every failure is returned immediately
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    // business errors (msg_cd) that are worth another try
+    pub fn retryable_msg_cd(mut self, msg_cd: String) -> Self {
+        self.retryable_msg_cds.push(msg_cd);
+        self
+    }
+
+    // `idempotent` is false for commands that change broker state (orders). Those are only
+    // retried when the request provably never reached the broker.
+    pub fn is_retryable(&self, error: &Error, idempotent: bool) -> bool {
+        match error {
+            Error::Transport(e) if e.is_connect() => true,
+            Error::Transport(e) => idempotent && (e.is_timeout() || e.is_request()),
+            Error::Status { status, .. } => idempotent && (*status >= 500 || *status == 429),
+            Error::Api { msg_cd, .. } if msg_cd == RATE_LIMITED_MSG_CD => true,
+            Error::Api { msg_cd, .. } => idempotent && self.retryable_msg_cds.contains(msg_cd),
+            _ => false,
+        }
+    }
+
+    // delay before retrying after `attempt` failed attempts (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let delay = exp.min(self.max_delay);
+        if self.jitter {
+            // randomize so concurrent callers don't retry in lockstep
+            delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            delay
+        }
+    }
+}
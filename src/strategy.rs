@@ -3,10 +3,28 @@ use crate::database::DataBase;
 pub mod algorithm;
 use algorithm::*;
 use std::collections::HashMap;
+use serde::Deserialize;
 
 pub enum Strategy {
     Test(Account),
-    PriceMomentum(Account),
+    PriceMomentum(Account, MomentumParams),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MomentumParams {
+    // most recent days left out of the momentum window
+    pub skip: usize,
+    pub lookback: usize,
+}
+
+impl Default for MomentumParams {
+    fn default() -> Self {
+        MomentumParams {
+            skip: 1,
+            lookback: 240,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -34,12 +52,12 @@ impl Account {
 
 pub trait TestStrategyIterator {
     type Item;
-    fn next(&mut self, idx: usize, account: &Account) -> Option<Self::Item>;
+    fn next(&mut self, _idx: usize, account: &Account) -> Option<Self::Item>;
 }
 
 impl TestStrategyIterator for DataBase {
     type Item = Vec<Box<dyn ApiCommand>>;
-    fn next(&mut self, idx: usize, account: &Account) -> Option<Self::Item> {
+    fn next(&mut self, _idx: usize, account: &Account) -> Option<Self::Item> {
         let price_cmd = Command::<Price>::new()
             .ticker("005930".to_string());
 
@@ -75,29 +93,29 @@ impl TestStrategyIterator for DataBase {
 
 pub trait PriceMomentumStrategyIterator {
     type Item;
-    fn next(&mut self, idx: usize, account: &mut Account) -> Option<Self::Item>;
+    fn next(&mut self, idx: usize, account: &mut Account, params: &MomentumParams) -> Option<Self::Item>;
 }
 
 impl PriceMomentumStrategyIterator for DataBase {
 
     type Item = Vec<Box<dyn ApiCommand>>;
-    fn next(&mut self, idx: usize, account: &mut Account) -> Option<Self::Item> {
+    fn next(&mut self, idx: usize, account: &mut Account, params: &MomentumParams) -> Option<Self::Item> {
 
         let mut res : Vec<(f64, &str)> = Vec::new();
         for stock in &self.stock_list {
-            let columns = self.get_columns(&stock).unwrap();
+            let columns = self.get_columns(stock).unwrap();
             if columns.len() <= idx {
                 continue;
             }
             let slice = &columns[idx..];
-            if let Some(momentum) = slice.get_momentum(1, 240) {
+            if let Some(momentum) = slice.get_momentum(params.skip, params.lookback) {
                 res.push((momentum, stock));
             }
         }
 
         res.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
 
-        let (best_momentum, best_stock) = &res[res.len() - 1];
+        let (_, best_stock) = &res[res.len() - 1];
 
         if idx == 0 {
             let order_buy_cmd = Command::<OrderBuy>::new()
@@ -1,23 +1,29 @@
 use crate::strategy::*;
 use crate::strategy::algorithm::Etc;
-use crate::Session;
+use crate::registry::SessionRegistry;
 use crate::database::DataBase;
 
 pub struct TimeRunner {
-    session: Session,
+    registry: SessionRegistry,
     database: DataBase,
 }
 
 impl TimeRunner {
-    pub fn new(session: Session) -> TimeRunner {
-        let database = DataBase::new();
+    pub fn new(registry: SessionRegistry) -> TimeRunner {
+        Self::with_database(registry, DataBase::new())
+    }
 
+    pub fn with_database(registry: SessionRegistry, database: DataBase) -> TimeRunner {
         TimeRunner {
-            session,
+            registry,
             database,
         }
     }
 
+    pub fn registry(&self) -> &SessionRegistry {
+        &self.registry
+    }
+
     pub fn run_back_test(&mut self, start_date: u32, end_date: u32, mut strategies: Vec<Strategy>) {
         let start_idx = self.database.idx_from_date(start_date);
         let end_idx = self.database.idx_from_date(end_date);
@@ -26,10 +32,10 @@ impl TimeRunner {
             for iter in &mut strategies {
                 let res = match iter {
                     Strategy::Test(account) => <DataBase as TestStrategyIterator>::next(&mut self.database, idx, account),
-                    Strategy::PriceMomentum(ref mut account) => <DataBase as PriceMomentumStrategyIterator>::next(&mut self.database, idx, account),
+                    Strategy::PriceMomentum(ref mut account, params) => <DataBase as PriceMomentumStrategyIterator>::next(&mut self.database, idx, account, params),
                 };
 
-                if let Some(commands) = res {
+                if let Some(_commands) = res {
 
                 }
             }
@@ -38,7 +44,7 @@ impl TimeRunner {
         for iter in &mut strategies {
             let account = match iter {
                 Strategy::Test(account) => account,
-                Strategy::PriceMomentum(account) => account
+                Strategy::PriceMomentum(account, _) => account
             };
             let stock_to_cash = self.database.calc_all_cell(&account.stocks);
             println!("stocks : {}, amount : {}, Total : {}", stock_to_cash, account.amount, stock_to_cash + account.amount);
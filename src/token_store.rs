@@ -0,0 +1,256 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs::File;
+use fs2::FileExt;
+use std::future::Future;
+use std::ops::Add;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Instant;
+use chrono::{Utc, NaiveDateTime, FixedOffset, Duration};
+use crate::{Error, Result};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// reissue a little before KIS actually rejects the token
+const TOKEN_EXPIRY_MARGIN_SECS: i64 = 60;
+// how long to wait for another process that is issuing a token
+const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const LOCK_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Token {
+    access_token : String,
+    #[serde(alias = "access_token_token_expired")]
+    expired : String,
+    token_type : String,
+    expires_in : u32,
+}
+
+impl Token {
+    pub fn is_expired(&self) -> bool {
+        let fixed = FixedOffset::east_opt(3600 * 9).unwrap();
+        let now: NaiveDateTime = Utc::now().naive_utc().add(fixed) + Duration::seconds(TOKEN_EXPIRY_MARGIN_SECS);
+        match NaiveDateTime::parse_from_str(&self.expired, "%Y-%m-%d %H:%M:%S") {
+            Ok(expired) => now > expired,
+            Err(_) => true,
+        }
+    }
+
+    pub(crate) fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    pub(crate) fn authorization(&self) -> String {
+        format!("{} {}", self.token_type, self.access_token)
+    }
+}
+
+// held while a token is being issued; other holders of the same store wait on it
+#[derive(Default)]
+pub struct TokenLock {
+    _file : Option<File>,
+}
+
+// where a Session keeps its access token between runs
+pub trait TokenStore: Send + Sync {
+    fn load<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<Option<Token>>>;
+    fn save<'a>(&'a self, app_key: &'a str, token: &'a Token) -> BoxFuture<'a, Result<()>>;
+    fn remove<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    // stores shared between processes must make issuers take turns
+    fn lock<'a>(&'a self, _app_key: &'a str) -> BoxFuture<'a, Result<TokenLock>> {
+        Box::pin(async { Ok(TokenLock::default()) })
+    }
+}
+
+// single json file; a token saved under another app key reads as missing
+pub struct FileTokenStore {
+    path : PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileTokenStore {
+            path: path.into(),
+        }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<Option<Token>>> {
+        Box::pin(read_token(self.path.clone(), app_key))
+    }
+
+    fn save<'a>(&'a self, app_key: &'a str, token: &'a Token) -> BoxFuture<'a, Result<()>> {
+        Box::pin(write_token(self.path.clone(), app_key, token))
+    }
+
+    // the file may hold the token of another app key sharing it
+    fn remove<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let _lock = lock_file(lock_path(&self.path)).await?;
+            if read_token(self.path.clone(), app_key).await?.is_some() {
+                remove_token(self.path.clone()).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn lock<'a>(&'a self, _app_key: &'a str) -> BoxFuture<'a, Result<TokenLock>> {
+        Box::pin(lock_file(lock_path(&self.path)))
+    }
+}
+
+// one json file per app key inside `dir`
+pub struct NamespacedFileTokenStore {
+    dir : PathBuf,
+}
+
+impl NamespacedFileTokenStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        NamespacedFileTokenStore {
+            dir: dir.into(),
+        }
+    }
+
+    pub fn path(&self, app_key: &str) -> PathBuf {
+        self.dir.join(format!("token-{:016x}.json", fnv1a(app_key.as_bytes())))
+    }
+}
+
+impl TokenStore for NamespacedFileTokenStore {
+    fn load<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<Option<Token>>> {
+        Box::pin(read_token(self.path(app_key), app_key))
+    }
+
+    fn save<'a>(&'a self, app_key: &'a str, token: &'a Token) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            write_token(self.path(app_key), app_key, token).await
+        })
+    }
+
+    fn remove<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(remove_token(self.path(app_key)))
+    }
+
+    fn lock<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<TokenLock>> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            lock_file(lock_path(&self.path(app_key))).await
+        })
+    }
+}
+
+// keeps tokens for the lifetime of the process only
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    tokens : Mutex<HashMap<String, Token>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<Option<Token>>> {
+        let token = self.tokens.lock().unwrap().get(app_key).cloned();
+        Box::pin(async move { Ok(token) })
+    }
+
+    fn save<'a>(&'a self, app_key: &'a str, token: &'a Token) -> BoxFuture<'a, Result<()>> {
+        self.tokens.lock().unwrap().insert(app_key.to_owned(), token.clone());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn remove<'a>(&'a self, app_key: &'a str) -> BoxFuture<'a, Result<()>> {
+        self.tokens.lock().unwrap().remove(app_key);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+// token file contents; the app key is kept as a hash so the file holds no credential
+#[derive(Deserialize, Serialize)]
+struct StoredToken {
+    #[serde(default)]
+    app_key_hash : String,
+    #[serde(flatten)]
+    token : Token,
+}
+
+fn app_key_hash(app_key: &str) -> String {
+    format!("{:016x}", fnv1a(app_key.as_bytes()))
+}
+
+async fn read_token(path: PathBuf, app_key: &str) -> Result<Option<Token>> {
+    let data = match tokio::fs::read_to_string(&path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    // a corrupt cache, or one issued for another app key, is treated like a missing one
+    let stored = serde_json::from_str::<StoredToken>(&data).ok()
+        .filter(|s| s.app_key_hash == app_key_hash(app_key));
+    Ok(stored.map(|s| s.token))
+}
+
+// write to a temp file and rename so readers never see a half written token
+async fn write_token(path: PathBuf, app_key: &str, token: &Token) -> Result<()> {
+    let data = serde_json::to_string(&StoredToken {
+        app_key_hash: app_key_hash(app_key),
+        token: token.clone(),
+    })?;
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    tokio::fs::write(&tmp, data).await?;
+    if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+async fn remove_token(path: PathBuf) -> Result<()> {
+    match tokio::fs::remove_file(&path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock = path.to_path_buf().into_os_string();
+    lock.push(".lock");
+    lock.into()
+}
+
+// advisory lock on a sidecar file, released when the TokenLock is dropped
+async fn lock_file(path: PathBuf) -> Result<TokenLock> {
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path).await?
+        .into_std().await;
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(()) => return Ok(TokenLock { _file: Some(file) }),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() && Instant::now() < deadline => {
+                tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+            },
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+                return Err(Error::Auth(format!("timed out waiting for token lock {}", path.display())));
+            },
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// stable across runs and rust versions, unlike DefaultHasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3))
+}
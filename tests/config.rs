@@ -0,0 +1,89 @@
+use trading::Environment;
+use trading::config::Config;
+use trading::strategy::Strategy;
+
+fn config(data: &str) -> Config {
+    let data = format!("data_dir = {:?}\n{}", std::env::temp_dir(), data);
+    Config::parse(&data).unwrap()
+}
+
+#[test]
+fn env_overrides_file() {
+    let mut config = config(r#"
+        environment = "paper"
+
+        [[accounts]]
+        name = "isa"
+        account_no = "12345678"
+        account_cd = "01"
+        capital = 1000000
+
+        [[strategies]]
+        kind = "price_momentum"
+        account = "isa"
+        params = { lookback = 120 }
+    "#);
+    config.apply_env(|key| match key {
+        "ISA_APP_KEY" => Some("key".to_string()),
+        "ISA_APP_SECRET" => Some("secret".to_string()),
+        "BACKTEST_END" => Some("20230301".to_string()),
+        _ => None,
+    }).unwrap();
+    config.validate().unwrap();
+
+    assert_eq!(config.environment(), Environment::Paper);
+    assert_eq!(config.backtest.end, 20230301);
+    assert_eq!(config.account("isa").unwrap().app_key, "key");
+    let account = config.account("isa").unwrap().to_account();
+    match config.strategies[0].to_strategy(account).unwrap() {
+        Strategy::PriceMomentum(account, params) => {
+            assert_eq!(account.amount, 1000000);
+            assert_eq!((params.skip, params.lookback), (1, 120));
+        },
+        _ => panic!("expected price_momentum"),
+    }
+}
+
+#[test]
+fn paper_domain_selects_paper_environment() {
+    let account = r#"
+        [[accounts]]
+        name = "personal"
+        app_key = "key"
+        app_secret = "secret"
+        account_no = "12345678"
+        account_cd = "01"
+    "#;
+    let mut config = config(account);
+    config.apply_env(|key| match key {
+        "DOMAIN" => Some("https://openapivts.koreainvestment.com:29443".to_string()),
+        _ => None,
+    }).unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.environment(), Environment::Paper);
+
+    config.environment = Some(Environment::Real);
+    assert!(config.validate().is_err());
+    config.domain = Some("http://127.0.0.1:8080".to_string());
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn invalid_settings_are_rejected() {
+    let account = r#"
+        [[accounts]]
+        name = "personal"
+        app_key = "key"
+        app_secret = "secret"
+        account_no = "12345678"
+        account_cd = "01"
+    "#;
+
+    assert!(Config::parse("unknown = 1").is_err());
+    assert!(Config::parse(r#"environment = "demo""#).is_err());
+    assert!(config("").validate().is_err());
+    assert!(config(account).validate().is_ok());
+    assert!(config(&format!("{}\n[backtest]\nstart = 20230512\nend = 20230101", account)).validate().is_err());
+    assert!(config(&format!("{}\n[[strategies]]\nkind = \"test\"\naccount = \"pension\"", account)).validate().is_err());
+    assert!(config(&format!("{}\n[[strategies]]\nkind = \"price_momentum\"\naccount = \"personal\"\nparams = {{ window = 3 }}", account)).validate().is_err());
+}
@@ -0,0 +1,27 @@
+use trading::Environment;
+
+#[test]
+fn paper_trading_tr_ids_use_v_prefix() {
+    assert_eq!(Environment::Paper.tr_id("TTTC0802U"), "VTTC0802U");
+    assert_eq!(Environment::Paper.tr_id("TTTC8434R"), "VTTC8434R");
+    assert_eq!(Environment::Real.tr_id("TTTC0802U"), "TTTC0802U");
+}
+
+#[test]
+fn quotation_tr_ids_are_shared() {
+    assert_eq!(Environment::Paper.tr_id("FHKST01010100"), "FHKST01010100");
+    assert_eq!(Environment::Real.tr_id("FHKST01010100"), "FHKST01010100");
+}
+
+#[test]
+fn environment_follows_domain() {
+    assert_eq!(Environment::from_domain("https://openapivts.koreainvestment.com:29443"), Environment::Paper);
+    assert_eq!(Environment::from_domain("https://openapi.koreainvestment.com:9443"), Environment::Real);
+}
+
+#[tokio::test]
+async fn session_rejects_domain_of_other_environment() {
+    let builder = trading::Session::builder_for("key".to_string(), "secret".to_string(), Environment::Real)
+        .domain("https://openapivts.koreainvestment.com:29443".to_string());
+    assert!(matches!(builder.build().await, Err(trading::Error::Config(_))));
+}
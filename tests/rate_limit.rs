@@ -0,0 +1,21 @@
+use std::time::{Duration, Instant};
+use trading::rate_limit::RateLimiter;
+
+#[tokio::test]
+async fn real_limiter_stays_under_kis_limit() {
+    let limiter = RateLimiter::real();
+    let start = Instant::now();
+    let mut sent = 0;
+    loop {
+        limiter.acquire().await;
+        if start.elapsed() >= Duration::from_secs(1) {
+            break;
+        }
+        sent += 1;
+    }
+
+    // KIS allows about 20 per second
+    assert!(sent <= 19, "{} requests in one second", sent);
+    assert!(sent >= 15, "only {} requests in one second", sent);
+    assert!(limiter.stats().throttled > 0);
+}
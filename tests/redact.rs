@@ -0,0 +1,47 @@
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::json;
+use trading::redact;
+
+#[test]
+fn headers_hide_credentials() {
+    let mut headers = HeaderMap::new();
+    headers.insert("appkey", HeaderValue::from_static("app-key"));
+    headers.insert("appsecret", HeaderValue::from_static("app-secret"));
+    headers.insert("authorization", HeaderValue::from_static("Bearer token"));
+    headers.insert("tr_id", HeaderValue::from_static("TTTC8434R"));
+
+    let headers = redact::headers(&headers);
+
+    let get = |key: &str| headers.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+    assert_eq!(get("appkey"), Some("***"));
+    assert_eq!(get("appsecret"), Some("***"));
+    assert_eq!(get("authorization"), Some("***"));
+    assert_eq!(get("tr_id"), Some("TTTC8434R"));
+}
+
+#[test]
+fn body_masks_account_numbers() {
+    let body = redact::value(&json!({ "CANO": "12345678", "ACNT_PRDT_CD": "01", "PDNO": "005930" }));
+
+    assert_eq!(body["CANO"], "******78");
+    assert_eq!(body["ACNT_PRDT_CD"], "01");
+    assert_eq!(body["PDNO"], "005930");
+}
+
+#[test]
+fn nested_values_are_masked() {
+    let body = redact::value(&json!({
+        "grant_type": "client_credentials",
+        "credentials": { "appkey": "app-key", "appsecret": "app-secret" },
+        "accounts": [{ "cano": "12345678" }, { "token": "mock-token" }],
+        "issued": { "access_token": "mock-token", "token_type": "Bearer" },
+    }));
+
+    assert_eq!(body["grant_type"], "client_credentials");
+    assert_eq!(body["credentials"]["appkey"], "***");
+    assert_eq!(body["credentials"]["appsecret"], "***");
+    assert_eq!(body["accounts"][0]["cano"], "******78");
+    assert_eq!(body["accounts"][1]["token"], "***");
+    assert_eq!(body["issued"]["access_token"], "***");
+    assert_eq!(body["issued"]["token_type"], "Bearer");
+}
@@ -0,0 +1,570 @@
+use std::sync::Arc;
+use std::time::Duration;
+use trading::{Session, Error};
+use trading::cassette::Cassette;
+use trading::command::*;
+use trading::mock::{MockServer, MockResponse};
+use trading::registry::SessionRegistry;
+use trading::rate_limit::RateLimiter;
+use trading::retry::RetryPolicy;
+use trading::strategy::Account;
+use trading::token_store::{MemoryTokenStore, NamespacedFileTokenStore};
+
+const PRICE_PATH: &str = "/uapi/domestic-stock/v1/quotations/inquire-price";
+const ORDER_PATH: &str = "/uapi/domestic-stock/v1/trading/order-cash";
+const BALANCE_PATH: &str = "/uapi/domestic-stock/v1/trading/inquire-balance";
+
+async fn session(server: &MockServer) -> Session {
+    Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(MemoryTokenStore::new())
+        .retry_policy(RetryPolicy::new().base_delay(Duration::from_millis(1)).jitter(false))
+        .build().await
+        .expect("create session failed")
+}
+
+fn order_buy() -> Command<OrderBuy> {
+    Command::<OrderBuy>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string())
+        .ticker("005930".to_string())
+        .count("1".to_string())
+}
+
+#[tokio::test]
+async fn execute_returns_output() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+
+    let price_cmd = Command::<Price>::new().ticker("005930".to_string());
+    let res = session.execute(&price_cmd).await.unwrap();
+
+    assert_eq!(res.output.unwrap()["stck_prpr"], "70000");
+    let requests = server.requests_to(PRICE_PATH);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].tr_id.as_deref(), Some("FHKST01010100"));
+    assert_eq!(requests[0].query["fid_input_iscd"], "005930");
+    assert_eq!(requests[0].headers["authorization"], "Bearer mock-token-1");
+}
+
+#[tokio::test]
+async fn business_error_is_typed() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    server.push(ORDER_PATH, MockResponse::api_error("APBK0919", "주문가능금액을 초과 했습니다"));
+
+    match session.execute(&order_buy()).await {
+        Err(Error::Api { msg_cd, .. }) => assert_eq!(msg_cd, "APBK0919"),
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[tokio::test]
+async fn expired_token_is_reissued_and_request_replayed() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    server.push(PRICE_PATH, MockResponse::api_error("EGW00123", "기간이 만료된 token 입니다."));
+
+    let price_cmd = Command::<Price>::new().ticker("005930".to_string());
+    session.execute(&price_cmd).await.unwrap();
+
+    assert_eq!(server.issued_tokens(), 2);
+    let requests = server.requests_to(PRICE_PATH);
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].headers["authorization"], "Bearer mock-token-2");
+}
+
+#[tokio::test]
+async fn quotations_are_retried_but_orders_are_not() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    server.push(PRICE_PATH, MockResponse::ok(serde_json::Value::Null).status(503));
+    server.push(ORDER_PATH, MockResponse::ok(serde_json::Value::Null).status(503));
+
+    let price_cmd = Command::<Price>::new().ticker("005930".to_string());
+    assert!(session.execute(&price_cmd).await.is_ok());
+    assert_eq!(server.requests_to(PRICE_PATH).len(), 2);
+
+    assert_eq!(session.execute(&order_buy()).await.unwrap_err().status(), Some(503));
+    assert_eq!(server.requests_to(ORDER_PATH).len(), 1);
+}
+
+#[tokio::test]
+async fn dropped_connection_is_a_transport_error() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    server.push(ORDER_PATH, MockResponse::disconnect());
+
+    assert!(session.execute(&order_buy()).await.unwrap_err().is_transport());
+}
+
+#[tokio::test]
+async fn transport_errors_leave_out_the_query() {
+    let server = MockServer::start().await.unwrap();
+    let session = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(MemoryTokenStore::new())
+        .retry_policy(RetryPolicy::none())
+        .build().await.unwrap();
+    server.push(BALANCE_PATH, MockResponse::disconnect());
+
+    let balance_cmd = Command::<Balance>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string());
+    let err = session.execute(&balance_cmd).await.unwrap_err();
+
+    assert!(err.is_transport());
+    assert!(!err.to_string().contains("12345678"), "{}", err);
+}
+
+#[tokio::test]
+async fn hashkey_is_sent_on_orders_only() {
+    let server = MockServer::start().await.unwrap();
+    let session = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(MemoryTokenStore::new())
+        .hashkey(true)
+        .build().await.unwrap();
+
+    session.execute(&order_buy()).await.unwrap();
+    session.execute(&Command::<Price>::new().ticker("005930".to_string())).await.unwrap();
+
+    let hashed = server.requests_to("/uapi/hashkey");
+    assert_eq!(hashed.len(), 1);
+    assert_eq!(hashed[0].body.as_ref().unwrap()["PDNO"], "005930");
+    assert_eq!(server.requests_to(ORDER_PATH)[0].headers["hashkey"], "mock-hash");
+    assert!(!server.requests_to(PRICE_PATH)[0].headers.contains_key("hashkey"));
+}
+
+#[tokio::test]
+async fn timed_out_quotations_are_retried_but_orders_are_not() {
+    let server = MockServer::start().await.unwrap();
+    let session = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(MemoryTokenStore::new())
+        .retry_policy(RetryPolicy::new().base_delay(Duration::from_millis(1)).jitter(false))
+        .timeout(Duration::from_millis(200))
+        .build().await.unwrap();
+    let stalled = || MockResponse::output(serde_json::json!({})).delay(Duration::from_secs(2));
+    server.push(PRICE_PATH, stalled());
+    server.push(ORDER_PATH, stalled());
+
+    let price_cmd = Command::<Price>::new().ticker("005930".to_string());
+    assert!(session.execute(&price_cmd).await.is_ok());
+    assert_eq!(server.requests_to(PRICE_PATH).len(), 2);
+
+    match session.execute(&order_buy()).await {
+        Err(Error::Transport(e)) => assert!(e.is_timeout()),
+        res => panic!("expected a timeout, got {:?}", res.map(|r| r.rt_cd)),
+    }
+    assert_eq!(server.requests_to(ORDER_PATH).len(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_results_keep_command_order() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    // the first command answers last
+    let tickers = ["005930", "000660", "035420"];
+    server.push_matching(PRICE_PATH, "fid_input_iscd", tickers[0],
+        MockResponse::output(serde_json::json!({ "stck_prpr": "1" })).delay(Duration::from_millis(300)));
+    server.push_matching(PRICE_PATH, "fid_input_iscd", tickers[1], MockResponse::api_error("EGW00000", "fail"));
+    server.push_matching(PRICE_PATH, "fid_input_iscd", tickers[2], MockResponse::output(serde_json::json!({ "stck_prpr": "3" })));
+
+    let commands: Vec<Box<dyn ApiCommand>> = tickers.iter()
+        .map(|t| Box::new(Command::<Price>::new().ticker(t.to_string())) as Box<dyn ApiCommand>)
+        .collect();
+    let results = session.execute_concurrent(&commands, 3).await;
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().output.as_ref().unwrap()["stck_prpr"], "1");
+    assert_eq!(results[1].as_ref().unwrap_err().msg_cd(), Some("EGW00000"));
+    assert_eq!(results[2].as_ref().unwrap().output.as_ref().unwrap()["stck_prpr"], "3");
+}
+
+#[tokio::test]
+async fn recorded_session_replays_without_network() {
+    let path = std::env::temp_dir().join(format!("trading-cassette-{}.jsonl", std::process::id()));
+    let price_cmd = Command::<Price>::new().ticker("005930".to_string());
+    {
+        let server = MockServer::start().await.unwrap();
+        server.push(ORDER_PATH, MockResponse::api_error("APBK0919", "주문가능금액을 초과 했습니다"));
+        let session = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+            .token_store(MemoryTokenStore::new())
+            .cassette(Cassette::record(&path).await.unwrap())
+            .build().await.unwrap();
+        session.execute(&price_cmd).await.unwrap();
+        session.execute(&order_buy()).await.unwrap_err();
+    }
+
+    let session = Session::builder("app-key".to_string(), "app-secret".to_string(), "http://127.0.0.1:9".to_string())
+        .cassette(Cassette::replay(&path).await.unwrap())
+        .build().await.unwrap();
+    let res = session.execute(&price_cmd).await.unwrap();
+    assert_eq!(res.output.unwrap()["stck_prpr"], "70000");
+    assert_eq!(session.execute(&order_buy()).await.unwrap_err().msg_cd(), Some("APBK0919"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_follows_recorded_token_refresh() {
+    let path = std::env::temp_dir().join(format!("trading-cassette-token-{}.jsonl", std::process::id()));
+    let price_cmd = Command::<Price>::new().ticker("005930".to_string());
+    {
+        let server = MockServer::start().await.unwrap();
+        server.push(PRICE_PATH, MockResponse::api_error("EGW00123", "기간이 만료된 token 입니다."));
+        let session = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+            .token_store(MemoryTokenStore::new())
+            .cassette(Cassette::record(&path).await.unwrap())
+            .build().await.unwrap();
+        assert_eq!(session.execute(&price_cmd).await.unwrap().rt_cd, "0");
+    }
+
+    let session = Session::builder("app-key".to_string(), "app-secret".to_string(), "http://127.0.0.1:9".to_string())
+        .cassette(Cassette::replay(&path).await.unwrap())
+        .build().await.unwrap();
+    assert_eq!(session.execute(&price_cmd).await.unwrap().rt_cd, "0");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn execute_all_follows_continuation_keys() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    let page = |rows: serde_json::Value, fk: &str, nk: &str| MockResponse::ok(serde_json::json!({
+        "rt_cd": "0",
+        "msg_cd": "KIOK0510",
+        "msg1": "조회가 완료되었습니다",
+        "ctx_area_fk100": fk,
+        "ctx_area_nk100": nk,
+        "output1": rows,
+        "output2": [{ "dnca_tot_amt": "1000" }],
+    }));
+    server.push(BALANCE_PATH, page(serde_json::json!([{ "pdno": "005930" }]), "FK1", "NK1").header("tr_cont", "M"));
+    server.push(BALANCE_PATH, page(serde_json::json!([{ "pdno": "000660" }]), "", "").header("tr_cont", "D"));
+
+    let balance_cmd = Command::<Balance>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string());
+    let res = session.execute_all(&balance_cmd).await.unwrap();
+
+    assert_eq!(res.output1.unwrap().as_array().unwrap().len(), 2);
+    let requests = server.requests_to(BALANCE_PATH);
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].headers["tr_cont"], "N");
+    assert_eq!(requests[1].query["CTX_AREA_NK100"], "NK1");
+    assert_eq!(requests[1].query["CANO"], "12345678");
+}
+
+#[tokio::test]
+async fn execute_all_fails_instead_of_truncating() {
+    let server = MockServer::start().await.unwrap();
+    let session = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(MemoryTokenStore::new())
+        .rate_limiter(RateLimiter::new(1000.0, 1))
+        .build().await.unwrap();
+    for _ in 0..100 {
+        server.push(BALANCE_PATH, MockResponse::ok(serde_json::json!({
+            "rt_cd": "0",
+            "msg_cd": "KIOK0510",
+            "msg1": "조회가 완료되었습니다",
+            "ctx_area_nk100": "NK",
+            "output1": [{ "pdno": "005930" }],
+        })).header("tr_cont", "M"));
+    }
+
+    let balance_cmd = Command::<Balance>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string());
+
+    assert!(matches!(session.execute_all(&balance_cmd).await, Err(Error::Truncated { pages: 100 })));
+}
+
+#[tokio::test]
+async fn token_errors_hide_the_token() {
+    let server = MockServer::start().await.unwrap();
+    server.push("/oauth2/tokenP", MockResponse::ok(serde_json::json!({ "access_token": "secret-token", "token_type": "Bearer" })));
+
+    let err = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(MemoryTokenStore::new())
+        .build().await
+        .err().unwrap();
+
+    assert!(matches!(err, Error::Auth(_)));
+    assert!(!err.to_string().contains("secret-token"), "{}", err);
+}
+
+#[tokio::test]
+async fn close_revokes_token() {
+    let server = MockServer::start().await.unwrap();
+    let dir = std::env::temp_dir().join(format!("trading-close-{}", std::process::id()));
+    let store = NamespacedFileTokenStore::new(&dir);
+    let token_path = store.path("app-key");
+    let session = Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(store)
+        .build().await.unwrap();
+    assert!(token_path.exists());
+
+    session.close().await.unwrap();
+
+    let requests = server.requests_to("/oauth2/revokeP");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].body.as_ref().unwrap()["token"], "mock-token-1");
+    assert!(!token_path.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn close_leaves_shared_token_alone() {
+    let server = MockServer::start().await.unwrap();
+    let dir = std::env::temp_dir().join(format!("trading-shared-{}", std::process::id()));
+    let build = || Session::builder("app-key".to_string(), "app-secret".to_string(), server.url())
+        .token_store(NamespacedFileTokenStore::new(&dir))
+        .build();
+    let live = build().await.unwrap();
+    let backtest = build().await.unwrap();
+    assert_eq!(server.issued_tokens(), 1);
+
+    backtest.close().await.unwrap();
+
+    assert!(server.requests_to("/oauth2/revokeP").is_empty());
+    assert!(NamespacedFileTokenStore::new(&dir).path("app-key").exists());
+    live.close().await.unwrap();
+    assert_eq!(server.requests_to("/oauth2/revokeP").len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn registry_aggregates_accounts() {
+    let server = MockServer::start().await.unwrap();
+    let session = Arc::new(session(&server).await);
+    let mut registry = SessionRegistry::new();
+    registry.register("personal".to_string(), Account::new("11111111".to_string(), "01".to_string(), 0), session.clone());
+    registry.register("isa".to_string(), Account::new("22222222".to_string(), "01".to_string(), 0), session.clone());
+
+    assert_eq!(registry.total_net_asset().await.unwrap(), 5000000);
+    let isa = &registry.get("isa").unwrap().account;
+    assert!(registry.session_for(isa).is_some());
+
+    registry.close().await.unwrap();
+    assert_eq!(server.requests_to("/oauth2/revokeP").len(), 1);
+}
+
+#[tokio::test]
+async fn execute_typed_parses_price() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+
+    let price_cmd = Command::<Price>::new().ticker("005930".to_string());
+    let price = session.execute_typed(&price_cmd).await.unwrap();
+
+    assert_eq!(price.price, 70000);
+    assert_eq!(price.change, 500);
+    assert_eq!(price.change_rate, 0.72);
+    assert_eq!(price.volume, 1000000);
+    assert_eq!(price.per, 0.0);
+}
+
+#[tokio::test]
+async fn daily_chart_rows_become_columns() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    server.push("/uapi/domestic-stock/v1/quotations/inquire-daily-itemchartprice", MockResponse::ok(serde_json::json!({
+        "rt_cd": "0",
+        "msg_cd": "MCA00000",
+        "msg1": "정상처리 되었습니다.",
+        "output1": { "stck_prpr": "70000" },
+        "output2": [
+            { "stck_bsop_date": "20230512", "stck_oprc": "64900", "stck_hgpr": "65000", "stck_lwpr": "63900",
+              "stck_clpr": "64100", "acml_vol": "8705434", "acml_tr_pbmn": "558915834400", "prdy_vrss": "-100" },
+            {},
+        ],
+    })));
+
+    let daily_value_cmd = Command::<DailyValue>::new()
+        .ticker("005930".to_string())
+        .date("20230512".to_string(), "20230512".to_string())
+        .period(Period::Day);
+    let columns = session.execute_typed(&daily_value_cmd).await.unwrap();
+
+    assert_eq!(columns.len(), 1);
+    assert_eq!(columns[0].date, "2023-05-12");
+    assert_eq!(columns[0].close_price, 64100);
+    assert_eq!(columns[0].amount, 558915834400);
+    assert!((columns[0].performance - -0.1558).abs() < 0.001);
+}
+
+#[tokio::test]
+async fn daily_price_rows_become_columns() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    server.push("/uapi/domestic-stock/v1/quotations/inquire-daily-price", MockResponse::output(serde_json::json!([
+        { "stck_bsop_date": "20230512", "stck_oprc": "64900", "stck_hgpr": "65000", "stck_lwpr": "63900",
+          "stck_clpr": "64100", "acml_vol": "8705434", "prdy_vrss": "-100", "prdy_ctrt": "-0.16" },
+    ])));
+
+    let daily_price_cmd = Command::<DailyPrice>::new()
+        .ticker("005930".to_string())
+        .period(Period::Day);
+    let columns = session.execute_typed(&daily_price_cmd).await.unwrap();
+
+    assert_eq!(columns.len(), 1);
+    assert_eq!(columns[0].date, "2023-05-12");
+    assert_eq!((columns[0].open_price, columns[0].close_price, columns[0].volume), (64900, 64100, 8705434));
+    assert_eq!(columns[0].amount, 0);
+    assert!((columns[0].performance - -0.16).abs() < 0.0001);
+}
+
+#[tokio::test]
+async fn balance_converts_to_account() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    server.push(BALANCE_PATH, MockResponse::ok(serde_json::json!({
+        "rt_cd": "0",
+        "msg_cd": "KIOK0510",
+        "msg1": "조회가 완료되었습니다",
+        "output1": [
+            { "pdno": "005930", "prdt_name": "삼성전자", "hldg_qty": "3", "pchs_avg_pric": "64100.0000",
+              "prpr": "65000", "evlu_amt": "195000", "evlu_pfls_amt": "2700", "evlu_pfls_rt": "1.40" },
+            { "pdno": "000660", "hldg_qty": "0" },
+        ],
+        "output2": [{ "dnca_tot_amt": "500000", "prvs_rcdl_excc_amt": "300000", "tot_evlu_amt": "495000", "nass_amt": "495000" }],
+    })));
+
+    let balance_cmd = Command::<Balance>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string());
+    let balance = session.execute_all_typed(&balance_cmd).await.unwrap();
+
+    assert_eq!(balance.holdings[0].avg_price, 64100.0);
+    assert_eq!(balance.summary.net_asset, 495000);
+    let account = balance.to_account("12345678".to_string(), "01".to_string()).unwrap();
+    assert_eq!(account.amount, 300000);
+    assert_eq!(account.stocks.len(), 1);
+    assert_eq!(account.stocks["005930"], 3);
+
+    let mut large = balance.clone();
+    large.summary.settled_deposit = 3_000_000_000;
+    assert!(matches!(large.to_account("12345678".to_string(), "01".to_string()), Err(Error::Decode(_))));
+}
+
+#[tokio::test]
+async fn order_type_and_price_are_validated() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+
+    let limit = order_buy().order_type(OrderType::Limit);
+    assert!(matches!(session.execute(&limit).await, Err(Error::Invalid(_))));
+    let market = order_buy().price("70000".to_string());
+    assert!(matches!(session.execute(&market).await, Err(Error::Invalid(_))));
+    assert!(server.requests_to(ORDER_PATH).is_empty());
+
+    session.execute(&order_buy().order_type(OrderType::Limit).price("70000".to_string())).await.unwrap();
+    let body = server.requests_to(ORDER_PATH)[0].body.clone().unwrap();
+    assert_eq!((body["ORD_DVSN"].as_str(), body["ORD_UNPR"].as_str()), (Some("00"), Some("70000")));
+    assert!(body.get("order_type").is_none());
+    assert_eq!(body["CANO"], "12345678");
+}
+
+#[tokio::test]
+async fn resting_order_can_be_modified_and_cancelled() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    let order = session.execute_typed(&order_buy().order_type(OrderType::Limit).price("69000".to_string())).await.unwrap();
+    assert_eq!((order.branch.as_str(), order.order_no.as_str()), ("91252", "0000117057"));
+
+    let revise = |branch: String, order_no: String| Command::<OrderModifyCancel>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string())
+        .original_order(branch, order_no);
+    let modified = session.execute_typed(&revise(order.branch.clone(), order.order_no.clone())
+        .modify(OrderType::Limit, "69500".to_string())).await.unwrap();
+    session.execute(&revise(modified.branch, modified.order_no).count("1".to_string())).await.unwrap();
+    let missing = revise(String::new(), String::new());
+    assert!(matches!(session.execute(&missing).await, Err(Error::Invalid(_))));
+
+    let bodies: Vec<_> = server.requests_to("/uapi/domestic-stock/v1/trading/order-rvsecncl")
+        .into_iter().map(|r| r.body.unwrap()).collect();
+    assert_eq!(bodies.len(), 2);
+    assert_eq!((bodies[0]["RVSE_CNCL_DVSN_CD"].as_str(), bodies[0]["ORD_UNPR"].as_str()), (Some("01"), Some("69500")));
+    assert_eq!(bodies[1]["ORGN_ODNO"], "0000117058");
+    assert_eq!(bodies[1]["CANO"], "12345678");
+    assert_eq!((bodies[1]["RVSE_CNCL_DVSN_CD"].as_str(), bodies[1]["QTY_ALL_ORD_YN"].as_str()), (Some("02"), Some("N")));
+}
+
+#[tokio::test]
+async fn daily_executions_are_collected_across_pages() {
+    const CCLD_PATH: &str = "/uapi/domestic-stock/v1/trading/inquire-daily-ccld";
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    let page = |rows: serde_json::Value, nk: &str| MockResponse::ok(serde_json::json!({
+        "rt_cd": "0",
+        "msg_cd": "KIOK0510",
+        "msg1": "조회가 완료되었습니다",
+        "ctx_area_fk100": "",
+        "ctx_area_nk100": nk,
+        "output1": rows,
+        "output2": { "tot_ccld_qty": "5" },
+    }));
+    server.push(CCLD_PATH, page(serde_json::json!([
+        { "ord_dt": "20230512", "odno": "0000117057", "sll_buy_dvsn_cd": "02", "pdno": "005930",
+          "ord_qty": "3", "ord_unpr": "69000", "tot_ccld_qty": "3", "avg_prvs": "68950.0", "rmn_qty": "0", "cncl_yn": "N" },
+    ]), "NK1").header("tr_cont", "M"));
+    server.push(CCLD_PATH, page(serde_json::json!([
+        { "ord_dt": "20230511", "odno": "0000116001", "sll_buy_dvsn_cd": "01", "pdno": "000660",
+          "ord_qty": "2", "tot_ccld_qty": "2", "rmn_qty": "0" },
+    ]), "").header("tr_cont", "D"));
+
+    let ccld_cmd = Command::<DailyExecutions>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string())
+        .date("20230501".to_string(), "20230512".to_string())
+        .fill_status(FillStatus::Filled);
+    let executions = session.execute_all_typed(&ccld_cmd).await.unwrap();
+
+    assert_eq!(executions.len(), 2);
+    assert!(executions[0].is_buy() && !executions[1].is_buy());
+    assert_eq!((executions[0].filled_quantity, executions[0].avg_price), (3, 68950.0));
+    let requests = server.requests_to(CCLD_PATH);
+    assert_eq!((requests[0].query["CCLD_DVSN"].as_str(), requests[1].query["CTX_AREA_NK100"].as_str()), ("01", "NK1"));
+    assert_eq!(requests[0].query["CANO"], "12345678");
+
+    // 2023 is more than three months back, so the past-history tr_id is used
+    assert_eq!(requests[0].tr_id.as_deref(), Some("CTSC9115R"));
+    let today = chrono::Local::now().format("%Y%m%d").to_string();
+    let recent = Command::<DailyExecutions>::new().date(today.clone(), today);
+    assert_eq!(recent.tr_id, "TTTC8001R");
+
+    let reversed = ccld_cmd.date("20230512".to_string(), "20230501".to_string());
+    assert!(matches!(session.execute(&reversed).await, Err(Error::Invalid(_))));
+}
+
+#[tokio::test]
+async fn orderable_amount_at_limit_price() {
+    const PSBL_PATH: &str = "/uapi/domestic-stock/v1/trading/inquire-psbl-order";
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+    let psbl_cmd = || Command::<OrderableAmount>::new()
+        .account_no("12345678".to_string())
+        .account_cd("01".to_string())
+        .ticker("005930".to_string());
+
+    let amount = session.execute_typed(&psbl_cmd().order_type(OrderType::Limit).price("70000".to_string())).await.unwrap();
+    assert_eq!((amount.buyable_quantity, amount.cash), (35, 2500000));
+    let query = &server.requests_to(PSBL_PATH)[0].query;
+    assert_eq!((query["ORD_DVSN"].as_str(), query["ORD_UNPR"].as_str()), ("00", "70000"));
+    assert_eq!(query["CANO"], "12345678");
+
+    assert!(matches!(session.execute(&psbl_cmd().order_type(OrderType::Limit)).await, Err(Error::Invalid(_))));
+}
+
+#[tokio::test]
+async fn asking_price_ladder() {
+    let server = MockServer::start().await.unwrap();
+    let session = session(&server).await;
+
+    let book = session.execute_typed(&Command::<AskingPrice>::new().ticker("005930".to_string())).await.unwrap();
+
+    assert_eq!((book.asks.len(), book.bids.len()), (10, 10));
+    assert_eq!(book.best_ask().unwrap().price, 70100);
+    assert_eq!(book.best_bid().unwrap().price, 70000);
+    assert_eq!(book.spread(), Some(100));
+    assert_eq!(book.bids[9].quantity, 15000);
+    assert_eq!((book.total_ask_quantity, book.expected_price, book.price), (55000, 0, 70000));
+}
@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+use trading::token_store::{FileTokenStore, NamespacedFileTokenStore, Token, TokenStore};
+
+fn token(access_token: &str) -> Token {
+    serde_json::from_value(serde_json::json!({
+        "access_token": access_token,
+        "access_token_token_expired": "2099-12-31 23:59:59",
+        "token_type": "Bearer",
+        "expires_in": 86400,
+    })).unwrap()
+}
+
+fn access_token(token: Option<Token>) -> Option<String> {
+    token.map(|t| serde_json::to_value(t).unwrap()["access_token"].as_str().unwrap().to_string())
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("trading-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn file_store_ignores_token_of_other_app_key() {
+    let dir = temp_dir("file-store");
+    let store = FileTokenStore::new(dir.join("token.json"));
+
+    store.save("key-a", &token("token-a")).await.unwrap();
+
+    assert_eq!(access_token(store.load("key-a").await.unwrap()).as_deref(), Some("token-a"));
+    assert!(store.load("key-b").await.unwrap().is_none());
+    store.remove("key-b").await.unwrap();
+    assert_eq!(access_token(store.load("key-a").await.unwrap()).as_deref(), Some("token-a"));
+    store.remove("key-a").await.unwrap();
+    assert!(store.load("key-a").await.unwrap().is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn namespaced_store_keeps_app_keys_apart() {
+    let dir = temp_dir("namespaced-store");
+    let store = NamespacedFileTokenStore::new(&dir);
+
+    store.save("key-a", &token("token-a")).await.unwrap();
+    store.save("key-b", &token("token-b")).await.unwrap();
+
+    assert_ne!(store.path("key-a"), store.path("key-b"));
+    assert_eq!(access_token(store.load("key-a").await.unwrap()).as_deref(), Some("token-a"));
+    assert_eq!(access_token(store.load("key-b").await.unwrap()).as_deref(), Some("token-b"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn lock_waits_for_other_holder() {
+    let dir = temp_dir("lock");
+    let first = NamespacedFileTokenStore::new(&dir);
+    let second = NamespacedFileTokenStore::new(&dir);
+
+    let held = first.lock("key-a").await.unwrap();
+    let started = std::time::Instant::now();
+    let waiter = tokio::spawn(async move {
+        second.lock("key-a").await.unwrap();
+        started.elapsed()
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!waiter.is_finished());
+    drop(held);
+
+    assert!(waiter.await.unwrap() >= std::time::Duration::from_millis(300));
+    // another app key is not blocked
+    let _held = first.lock("key-a").await.unwrap();
+    first.lock("key-b").await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn save_replaces_file_without_leftovers() {
+    let dir = temp_dir("atomic");
+    let store = NamespacedFileTokenStore::new(&dir);
+    std::fs::write(store.path("key-a"), "{ not json").unwrap();
+    assert!(store.load("key-a").await.unwrap().is_none());
+
+    store.save("key-a", &token("token-a")).await.unwrap();
+    store.save("key-a", &token("token-b")).await.unwrap();
+
+    assert_eq!(access_token(store.load("key-a").await.unwrap()).as_deref(), Some("token-b"));
+    let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(files, vec![store.path("key-a").file_name().unwrap().to_owned()]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}